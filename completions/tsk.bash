@@ -2,7 +2,12 @@ _tsk() {
     local cur prev words cword
     _init_completion || return
 
-    local commands="init create list show update start done remove m completions"
+    local commands="init create list show update edit next agenda context search tree claim start heartbeat stop done reopen cancel status workflow remove depend label link unlink comment comments log undo doctor m completions"
+
+    if [[ $cur == --json* ]]; then
+        COMPREPLY=($(compgen -W "--json --jsonl" -- "$cur"))
+        return
+    fi
 
     if [[ $cword -eq 1 ]]; then
         COMPREPLY=($(compgen -W "$commands" -- "$cur"))
@@ -12,7 +17,7 @@ _tsk() {
     local cmd="${words[1]}"
 
     case $cmd in
-        show|start|done|remove)
+        show|stop|done|reopen|cancel|status|comment|comments)
             if [[ $cword -eq 2 ]]; then
                 local ids=$(tsk ids 2>/dev/null)
                 COMPREPLY=($(compgen -W "$ids" -- "$cur"))
@@ -22,6 +27,26 @@ _tsk() {
             if [[ $cword -eq 2 ]]; then
                 local ids=$(tsk ids 2>/dev/null)
                 COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            elif [[ $cur == -* ]]; then
+                COMPREPLY=($(compgen -W "--priority" -- "$cur"))
+            fi
+            ;;
+        edit)
+            if [[ $cword -eq 2 ]]; then
+                local ids=$(tsk ids 2>/dev/null)
+                COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            else
+                case $prev in
+                    --parent|--depend)
+                        local ids=$(tsk ids 2>/dev/null)
+                        COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+                        ;;
+                    *)
+                        if [[ $cur == -* ]]; then
+                            COMPREPLY=($(compgen -W "--title --description --priority --parent --no-parent --depend --no-depend --due --no-due" -- "$cur"))
+                        fi
+                        ;;
+                esac
             fi
             ;;
         create)
@@ -32,7 +57,7 @@ _tsk() {
                     ;;
                 *)
                     if [[ $cur == -* ]]; then
-                        COMPREPLY=($(compgen -W "--parent --depend" -- "$cur"))
+                        COMPREPLY=($(compgen -W "--parent --depend --priority --label --due --force --unique" -- "$cur"))
                     fi
                     ;;
             esac
@@ -45,11 +70,111 @@ _tsk() {
                     ;;
                 *)
                     if [[ $cur == -* ]]; then
-                        COMPREPLY=($(compgen -W "--inprogress --all --parent" -- "$cur"))
+                        COMPREPLY=($(compgen -W "--inprogress --all --parent --status --label --overdue --due-before --mine" -- "$cur"))
+                    fi
+                    ;;
+            esac
+            ;;
+        depend)
+            if [[ $cword -eq 2 ]]; then
+                COMPREPLY=($(compgen -W "add rm" -- "$cur"))
+            elif [[ $cword -ge 3 ]]; then
+                local ids=$(tsk ids 2>/dev/null)
+                COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            fi
+            ;;
+        remove)
+            case $prev in
+                --reparent-to)
+                    local ids=$(tsk ids 2>/dev/null)
+                    COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+                    ;;
+                *)
+                    if [[ $cur == -* ]]; then
+                        COMPREPLY=($(compgen -W "--recursive --orphan-children --reparent-to --dry-run --yes -y" -- "$cur"))
+                    elif [[ $cword -eq 2 ]]; then
+                        local ids=$(tsk ids 2>/dev/null)
+                        COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+                    fi
+                    ;;
+            esac
+            ;;
+        start)
+            if [[ $cur == -* ]]; then
+                COMPREPLY=($(compgen -W "--lease" -- "$cur"))
+            elif [[ $cword -eq 2 ]]; then
+                local ids=$(tsk ids 2>/dev/null)
+                COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            fi
+            ;;
+        search)
+            case $prev in
+                --kind)
+                    COMPREPLY=($(compgen -W "task memory" -- "$cur"))
+                    ;;
+                *)
+                    if [[ $cur == -* ]]; then
+                        COMPREPLY=($(compgen -W "--kind --status --limit" -- "$cur"))
                     fi
                     ;;
             esac
             ;;
+        context)
+            if [[ $cur == -* ]]; then
+                COMPREPLY=($(compgen -W "--budget" -- "$cur"))
+            fi
+            ;;
+        tree)
+            if [[ $cur == -* ]]; then
+                COMPREPLY=($(compgen -W "--depth --all" -- "$cur"))
+            elif [[ $cword -eq 2 ]]; then
+                local ids=$(tsk ids 2>/dev/null)
+                COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            fi
+            ;;
+        claim|heartbeat)
+            if [[ $cur == -* ]]; then
+                COMPREPLY=($(compgen -W "--as --lease" -- "$cur"))
+            elif [[ $cword -eq 2 ]]; then
+                local ids=$(tsk ids 2>/dev/null)
+                COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            fi
+            ;;
+        link|unlink)
+            if [[ $cword -eq 2 ]]; then
+                local ids=$(tsk ids 2>/dev/null)
+                COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            elif [[ $cword -eq 3 ]]; then
+                local ids=$(tsk m list 2>/dev/null | grep -oE '^\[[a-z0-9]{6}\]' | tr -d '[]')
+                COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            fi
+            ;;
+        label)
+            if [[ $cword -eq 2 ]]; then
+                COMPREPLY=($(compgen -W "add rm" -- "$cur"))
+            elif [[ $cword -eq 3 ]]; then
+                local ids=$(tsk ids 2>/dev/null)
+                COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            fi
+            ;;
+        log)
+            if [[ $cur == -* ]]; then
+                COMPREPLY=($(compgen -W "--all --last" -- "$cur"))
+            elif [[ $cword -eq 2 ]]; then
+                local ids=$(tsk ids 2>/dev/null)
+                COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+            fi
+            ;;
+        undo)
+            if [[ $cur == -* ]]; then
+                COMPREPLY=($(compgen -W "--dry-run" -- "$cur"))
+            fi
+            ;;
+        doctor)
+            if [[ $cur == -* ]]; then
+                COMPREPLY=($(compgen -W "--fix" -- "$cur"))
+            fi
+            ;;
         completions)
             if [[ $cword -eq 2 ]]; then
                 COMPREPLY=($(compgen -W "bash zsh fish powershell elvish" -- "$cur"))
@@ -58,23 +183,32 @@ _tsk() {
         m)
             if [[ $cword -eq 2 ]]; then
                 if [[ $cur == -* ]]; then
-                    COMPREPLY=($(compgen -W "--tags -t" -- "$cur"))
+                    COMPREPLY=($(compgen -W "--tags -t --task --kind --review-after --expires --force --unique" -- "$cur"))
                 else
-                    COMPREPLY=($(compgen -W "list show search rm" -- "$cur"))
+                    COMPREPLY=($(compgen -W "list tags review show edit search pin unpin export rm" -- "$cur"))
                 fi
             elif [[ $cword -eq 3 ]]; then
                 local subcmd="${words[2]}"
                 case $subcmd in
-                    show|rm)
+                    show|edit|pin|unpin|rm)
                         local ids=$(tsk m list 2>/dev/null | grep -oE '^\[[a-z0-9]{6}\]' | tr -d '[]')
                         COMPREPLY=($(compgen -W "$ids" -- "$cur"))
                         ;;
                     list)
                         if [[ $cur == -* ]]; then
-                            COMPREPLY=($(compgen -W "--tag --last" -- "$cur"))
+                            COMPREPLY=($(compgen -W "--tag --any --kind --stale --last" -- "$cur"))
                         fi
                         ;;
+                    export)
+                        COMPREPLY=($(compgen -W "--adr" -- "$cur"))
+                        ;;
                 esac
+            elif [[ $prev == --kind ]]; then
+                COMPREPLY=($(compgen -W "decision convention gotcha" -- "$cur"))
+            elif [[ ${words[2]} == export && $prev == --adr ]]; then
+                COMPREPLY=($(compgen -d -- "$cur"))
+            elif [[ ${words[2]} == edit && $cur == -* ]]; then
+                COMPREPLY=($(compgen -W "--content --kind --no-kind --review-after --no-review-after --expires --no-expires --tags --add-tag --rm-tag" -- "$cur"))
             fi
             ;;
     esac
@@ -1,25 +1,30 @@
 use anyhow::{bail, Context, Result};
-use clap::{CommandFactory, Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::{generate, Shell};
-use dialoguer::MultiSelect;
+use dialoguer::{Confirm, Editor, Input, MultiSelect, Select};
 use rand::Rng;
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs;
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::OnceLock;
 
 mod mcp;
 
-/// Task status
+/// Task status kind. Every workflow state has one of these kinds,
+/// stored in `tasks.done` as 0/1/2/3.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TaskStatus {
     Pending,
     InProgress,
     Done,
+    /// Closed without being done: does not satisfy dependencies
+    Cancelled,
 }
 
 impl TaskStatus {
@@ -27,6 +32,7 @@ impl TaskStatus {
         match value {
             0 => TaskStatus::Pending,
             1 => TaskStatus::InProgress,
+            3 => TaskStatus::Cancelled,
             _ => TaskStatus::Done,
         }
     }
@@ -36,6 +42,7 @@ impl TaskStatus {
             TaskStatus::Pending => 0,
             TaskStatus::InProgress => 1,
             TaskStatus::Done => 2,
+            TaskStatus::Cancelled => 3,
         }
     }
 
@@ -44,6 +51,7 @@ impl TaskStatus {
             TaskStatus::Pending => "pending",
             TaskStatus::InProgress => "in_progress",
             TaskStatus::Done => "done",
+            TaskStatus::Cancelled => "cancelled",
         }
     }
 
@@ -52,8 +60,159 @@ impl TaskStatus {
             TaskStatus::Pending => " ",
             TaskStatus::InProgress => ">",
             TaskStatus::Done => "x",
+            TaskStatus::Cancelled => "-",
         }
     }
+
+    /// Done or cancelled: no more work expected
+    pub fn is_closed(self) -> bool {
+        matches!(self, TaskStatus::Done | TaskStatus::Cancelled)
+    }
+}
+
+/// Workflow state defined in project config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowState {
+    pub name: String,
+    pub marker: String,
+    pub kind: TaskStatus,
+}
+
+/// Task workflow: states and allowed transitions between them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    pub states: Vec<WorkflowState>,
+    /// Allowed moves per state; when empty, any move is allowed
+    #[serde(default)]
+    pub transitions: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        let state = |kind: TaskStatus| WorkflowState {
+            name: kind.as_str().to_string(),
+            marker: kind.marker().to_string(),
+            kind,
+        };
+        let names = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        Workflow {
+            states: vec![
+                state(TaskStatus::Pending),
+                state(TaskStatus::InProgress),
+                state(TaskStatus::Done),
+                state(TaskStatus::Cancelled),
+            ],
+            transitions: BTreeMap::from([
+                ("pending".to_string(), names(&["in_progress", "done", "cancelled"])),
+                ("in_progress".to_string(), names(&["pending", "done", "cancelled"])),
+                ("done".to_string(), names(&["pending"])),
+                ("cancelled".to_string(), names(&["pending"])),
+            ]),
+        }
+    }
+}
+
+impl Workflow {
+    pub fn state(&self, name: &str) -> Option<&WorkflowState> {
+        self.states.iter().find(|s| s.name == name)
+    }
+
+    /// First state of the given kind, if the workflow has one
+    pub fn find_kind(&self, kind: TaskStatus) -> Option<&WorkflowState> {
+        self.states.iter().find(|s| s.kind == kind)
+    }
+
+    /// First state of a required kind (used for create/start/done)
+    pub fn first_of(&self, kind: TaskStatus) -> &WorkflowState {
+        self.find_kind(kind)
+            .expect("workflow validated to have pending, in_progress and done states")
+    }
+
+    pub fn kind_of(&self, name: &str) -> Option<TaskStatus> {
+        self.state(name).map(|s| s.kind)
+    }
+
+    pub fn marker(&self, name: &str) -> &str {
+        self.state(name).map(|s| s.marker.as_str()).unwrap_or("?")
+    }
+
+    pub fn allows(&self, from: &str, to: &str) -> bool {
+        self.transitions.is_empty()
+            || self
+                .transitions
+                .get(from)
+                .is_some_and(|targets| targets.iter().any(|t| t == to))
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut seen = HashSet::new();
+        for state in &self.states {
+            validate_state_name(&state.name)?;
+            if !seen.insert(state.name.as_str()) {
+                bail!("Workflow state '{}' is defined twice.", state.name);
+            }
+        }
+        for kind in [TaskStatus::Pending, TaskStatus::InProgress, TaskStatus::Done] {
+            if !self.states.iter().any(|s| s.kind == kind) {
+                bail!("Workflow needs at least one state of kind '{}'.", kind.as_str());
+            }
+        }
+        for (from, targets) in &self.transitions {
+            for name in std::iter::once(from).chain(targets) {
+                if self.state(name).is_none() {
+                    bail!("Workflow transition references unknown state '{}'.", name);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What `complete_task` does about parent tasks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParentPolicy {
+    /// Parents and children are completed independently
+    #[default]
+    Allow,
+    /// A parent can't be completed while it has open subtasks
+    Block,
+    /// A parent is completed when its last open subtask is completed or cancelled
+    Auto,
+}
+
+/// What creating a task or memory does when a similar one already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// Create it and report the close matches
+    #[default]
+    Warn,
+    /// Refuse and report the close matches
+    Refuse,
+}
+
+impl DuplicatePolicy {
+    /// Per-call override from `force` / `unique` flags; `None` uses the config
+    pub fn from_flags(force: bool, unique: bool) -> Option<Self> {
+        match (force, unique) {
+            (true, _) => Some(DuplicatePolicy::Warn),
+            (_, true) => Some(DuplicatePolicy::Refuse),
+            _ => None,
+        }
+    }
+}
+
+/// Project configuration (.tsk/config.json)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    pub workflow: Workflow,
+    #[serde(default)]
+    pub parent_completion: ParentPolicy,
+    #[serde(default)]
+    pub duplicates: DuplicatePolicy,
 }
 
 /// Task data structure
@@ -62,13 +221,44 @@ pub struct Task {
     pub id: String,
     pub title: String,
     pub description: String,
-    pub status: TaskStatus,
+    pub status: String,
+    pub priority: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub depends: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub overdue: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub depend_id: Option<String>,
+    pub started_at: Option<String>,
+    /// In-progress tasks past this time (UTC) return to pending
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_until: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<Progress>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<TaskComment>,
+    /// Memories linked to this task
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memories: Vec<Memory>,
+}
+
+/// Comment (progress note) on a task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskComment {
+    pub id: i64,
+    pub task_id: String,
+    pub text: String,
+    pub author: String,
+    pub created_at: String,
 }
 
 /// Task summary for list output
@@ -76,11 +266,321 @@ pub struct Task {
 pub struct TaskSummary {
     pub id: String,
     pub title: String,
-    pub status: TaskStatus,
+    pub status: String,
+    pub priority: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub depends: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub overdue: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<Progress>,
+}
+
+/// Subtask rollup for a parent task; cancelled subtasks don't count
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Progress {
+    pub done: i64,
+    pub total: i64,
+}
+
+impl Progress {
+    fn from_counts(done: i64, total: i64) -> Option<Progress> {
+        (total > 0).then_some(Progress { done, total })
+    }
+}
+
+/// One day of `tsk agenda`: open tasks due that day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgendaDay {
+    pub date: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub overdue: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub today: bool,
+    pub tasks: Vec<TaskSummary>,
+}
+
+/// Task with its subtasks, for `tsk tree`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskNode {
+    #[serde(flatten)]
+    pub task: TaskSummary,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TaskNode>,
+}
+
+/// Fields for `create_task`
+#[derive(Debug, Clone, Default)]
+pub struct NewTask<'a> {
+    pub title: &'a str,
+    pub description: &'a str,
+    pub parent: Option<&'a str>,
+    pub depends: &'a [String],
+    pub priority: i32,
+    pub labels: &'a [String],
+    /// Due date (YYYY-MM-DD, today, tomorrow or +N[d|w])
+    pub due: Option<&'a str>,
+    /// Overrides the configured duplicate policy
+    pub duplicates: Option<DuplicatePolicy>,
+}
+
+/// Filters for `list_tasks`
+#[derive(Debug, Clone, Default)]
+pub struct TaskFilter<'a> {
+    /// Only in-progress kind states
+    pub inprogress: bool,
+    /// All states (otherwise pending kind states only)
+    pub all: bool,
+    /// Only children of this task
+    pub parent: Option<&'a str>,
+    /// Only this workflow state (overrides inprogress/all)
+    pub status: Option<&'a str>,
+    /// Only tasks with this label
+    pub label: Option<&'a str>,
+    /// Only open tasks whose due date has passed
+    pub overdue: bool,
+    /// Only tasks due strictly before this date
+    pub due_before: Option<&'a str>,
+    /// Only tasks claimed by this agent
+    pub assignee: Option<&'a str>,
+}
+
+/// Field changes for `edit_task`; `None` leaves a field unchanged
+#[derive(Debug, Clone, Default)]
+pub struct TaskEdit<'a> {
+    pub title: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub priority: Option<i32>,
+    /// `Some(None)` clears the parent
+    pub parent: Option<Option<&'a str>>,
+    /// Replaces all dependencies; an empty list clears them
+    pub depends: Option<&'a [String]>,
+    /// `Some(None)` clears the due date
+    pub due: Option<Option<&'a str>>,
+}
+
+impl TaskEdit<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.description.is_none()
+            && self.priority.is_none()
+            && self.parent.is_none()
+            && self.depends.is_none()
+            && self.due.is_none()
+    }
+}
+
+/// What `remove_task` does with the subtasks of the removed task
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChildPolicy<'a> {
+    /// Refuse to remove a task that has subtasks
+    #[default]
+    Refuse,
+    /// Remove the whole subtree
+    Recursive,
+    /// Make the subtasks top-level tasks
+    Orphan,
+    /// Move the subtasks under another task
+    Reparent(&'a str),
+}
+
+/// Outcome (or preview) of `remove_task`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Removal {
+    /// Removed tasks, the requested one first
+    pub removed: Vec<TaskSummary>,
+    /// Subtasks moved out of the way instead of being removed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub moved: Vec<String>,
+    /// New parent of `moved`; `None` means they became top-level
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_parent: Option<String>,
+    /// Active tasks outside the removed set that depend on it; a real run
+    /// refuses while there are any, so only a preview lists them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocked: Vec<String>,
+}
+
+/// Entry in the task event log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskEvent {
+    pub id: i64,
+    pub task_id: String,
+    pub event: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_value: Option<String>,
+    pub actor: String,
+    pub created_at: String,
+}
+
+/// What a search result is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchKind {
+    Task,
+    Memory,
+}
+
+/// Full-text search result; matched terms in `snippet` are wrapped in `**`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub kind: SearchKind,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub depend_id: Option<String>,
+    pub status: Option<String>,
+    pub snippet: String,
+    /// Relevance relative to the best hit of the same kind, from 0 to 1
+    pub score: f64,
+}
+
+/// Existing task or memory that closely matches one being created
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Duplicate {
+    pub kind: SearchKind,
+    pub id: String,
+    /// Task title or memory content
+    pub text: String,
+    /// Trigram similarity from 0 to 1
+    pub similarity: f64,
+}
+
+/// `create_task` or `create_memory` refused because close matches exist
+#[derive(Debug)]
+pub struct DuplicateError(pub Vec<Duplicate>);
+
+impl std::fmt::Display for DuplicateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (what, edit) = match self.0.first().map(|d| d.kind) {
+            Some(SearchKind::Memory) => ("memory", "tsk m edit"),
+            _ => ("task", "tsk edit"),
+        };
+        writeln!(f, "Similar {} already exists:", what)?;
+        for duplicate in &self.0 {
+            writeln!(
+                f,
+                "  {}  {} ({:.0}% similar)",
+                duplicate.id,
+                truncate_content(&duplicate.text, 50),
+                duplicate.similarity * 100.0
+            )?;
+        }
+        write!(f, "Update it with '{} <id>' instead, or pass --force to create anyway.", edit)
+    }
+}
+
+impl std::error::Error for DuplicateError {}
+
+/// A newly created task or memory
+#[derive(Debug, Clone, Serialize)]
+pub struct Created {
+    pub id: String,
+    /// Close matches that already existed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub similar: Vec<Duplicate>,
+}
+
+/// Whether close matches should stop a create, given a per-call override
+fn refuse_duplicates(duplicates: Option<DuplicatePolicy>) -> Result<bool> {
+    let policy = match duplicates {
+        Some(policy) => policy,
+        None => project_config()?.duplicates,
+    };
+    Ok(policy == DuplicatePolicy::Refuse)
+}
+
+/// A memory tag and how many entries use it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
+/// Operation recorded in the undo journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoEntry {
+    pub id: i64,
+    pub operation: String,
+    pub actor: String,
+    pub created_at: String,
+}
+
+/// Kind of integrity problem found by `tsk doctor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    DuplicateId,
+    InvalidStatus,
+    OrphanParent,
+    OrphanDependency,
+    ParentCycle,
+    DependencyCycle,
+}
+
+impl IssueKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IssueKind::DuplicateId => "duplicate_id",
+            IssueKind::InvalidStatus => "invalid_status",
+            IssueKind::OrphanParent => "orphan_parent",
+            IssueKind::OrphanDependency => "orphan_dependency",
+            IssueKind::ParentCycle => "parent_cycle",
+            IssueKind::DependencyCycle => "dependency_cycle",
+        }
+    }
+}
+
+/// Integrity problem found by `tsk doctor`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub id: String,
+    pub detail: String,
+    pub fixed: bool,
+}
+
+/// What a memory entry records; entries without a kind are plain notes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryKind {
+    /// Architecture or design decision (exported as an ADR)
+    Decision,
+    /// Rule the project follows
+    Convention,
+    /// Pitfall to avoid
+    Gotcha,
+}
+
+impl MemoryKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MemoryKind::Decision => "decision",
+            MemoryKind::Convention => "convention",
+            MemoryKind::Gotcha => "gotcha",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "decision" => Ok(MemoryKind::Decision),
+            "convention" => Ok(MemoryKind::Convention),
+            "gotcha" => Ok(MemoryKind::Gotcha),
+            _ => bail!("Unknown kind '{}'. Valid: decision, convention, gotcha.", s),
+        }
+    }
 }
 
 /// Memory entry for storing project knowledge
@@ -89,9 +589,85 @@ pub struct Memory {
     pub id: String,
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tags: Option<String>,
+    pub kind: Option<MemoryKind>,
+    /// Always included in `tsk context`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Date from which the entry should be checked again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review_after: Option<String>,
+    /// Date from which the entry no longer holds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    /// Review date or expiry date has come
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    /// IDs of linked tasks (filled in by `get_memory`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tasks: Vec<String>,
+}
+
+/// Fields for `create_memory`
+#[derive(Debug, Clone, Copy)]
+pub struct NewMemory<'a> {
+    pub content: &'a str,
+    pub kind: Option<MemoryKind>,
+    pub tags: &'a [String],
+    /// Review date, in any form `--due` accepts
+    pub review_after: Option<&'a str>,
+    /// Expiry date, in any form `--due` accepts
+    pub expires_at: Option<&'a str>,
+    /// Tasks to link the memory to
+    pub tasks: &'a [String],
+    /// Overrides the configured duplicate policy
+    pub duplicates: Option<DuplicatePolicy>,
+}
+
+/// Changes for `edit_memory`; `None` and empty lists leave things unchanged
+#[derive(Debug, Clone, Default)]
+pub struct MemoryEdit<'a> {
+    pub content: Option<&'a str>,
+    /// `Some(None)` clears the kind
+    pub kind: Option<Option<MemoryKind>>,
+    /// `Some(None)` clears the review date
+    pub review_after: Option<Option<&'a str>>,
+    /// `Some(None)` clears the expiry date
+    pub expires_at: Option<Option<&'a str>>,
+    /// Replaces all tags; an empty list clears them
+    pub tags: Option<&'a [String]>,
+    pub add_tags: &'a [String],
+    pub remove_tags: &'a [String],
+}
+
+/// Filters for `list_memories`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryFilter<'a> {
+    /// Only entries with all of these tags (or any of them, with `any`)
+    pub tags: &'a [String],
+    pub any: bool,
+    pub kind: Option<MemoryKind>,
+    /// Only entries whose review or expiry date has come
+    pub stale: bool,
+    /// Only the newest N entries
+    pub last: Option<usize>,
+}
+
+impl MemoryEdit<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.content.is_none()
+            && self.kind.is_none()
+            && self.review_after.is_none()
+            && self.expires_at.is_none()
+            && self.tags.is_none()
+            && self.add_tags.is_empty()
+            && self.remove_tags.is_empty()
+    }
 }
 
 #[derive(Parser)]
@@ -111,6 +687,14 @@ struct Cli {
     /// Update tsk to the latest version from GitHub
     #[arg(long)]
     selfupdate: bool,
+
+    /// Print results as JSON (the shapes the MCP tools return)
+    #[arg(long, global = true, conflicts_with = "jsonl")]
+    json: bool,
+
+    /// Print results as JSON Lines, one list item per line
+    #[arg(long, global = true)]
+    jsonl: bool,
 }
 
 #[derive(Subcommand)]
@@ -130,15 +714,24 @@ Available agents: claude, copilot, cursor, windsurf")]
         #[arg(long)]
         rules: Option<String>,
     },
-    /// Create a new task [--parent <id>] [--depend <id>]
+    /// Create a new task [--parent <id>] [--depend <id>]...
     #[command(after_help = "Examples:
   tsk create \"Fix bug\" \"Fix login validation\"
   tsk create \"Subtask\" \"Details\" --parent a1b2c3
   tsk create \"Task\" \"Details\" --depend x7y8z9
+  tsk create \"Task\" \"Details\" --depend x7y8z9 --depend k4l5m6
+  tsk create \"Hotfix\" \"Details\" --priority 5
+  tsk create \"Task\" \"Details\" --label bug --label frontend
+  tsk create \"Release notes\" \"Details\" --due 2026-11-01
+
+Due dates: YYYY-MM-DD, today, tomorrow, +3d or +2w.
 
 Output symbols in list:
   ^id  parent task
-  @id  dependency")]
+  @id  dependency
+  !n   priority
+  #x   label
+  ~d   due date")]
     Create {
         /// Task title (short summary)
         title: String,
@@ -147,24 +740,46 @@ Output symbols in list:
         /// Parent task ID for subtasks/stories
         #[arg(long)]
         parent: Option<String>,
-        /// Dependency: this task can't be done until depend task is done
+        /// Dependency: this task can't be done until depend task is done (repeatable)
+        #[arg(long)]
+        depend: Vec<String>,
+        /// Priority (higher is more urgent, default 0)
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        priority: i32,
+        /// Label (e.g. bug, feature, frontend; repeatable)
+        #[arg(long)]
+        label: Vec<String>,
+        /// Due date (YYYY-MM-DD, today, tomorrow, +3d, +2w)
         #[arg(long)]
-        depend: Option<String>,
+        due: Option<String>,
+        /// Create even if a similar open task exists and the config refuses duplicates
+        #[arg(long, conflicts_with = "unique")]
+        force: bool,
+        /// Refuse if a similar open task exists under the same parent
+        #[arg(long)]
+        unique: bool,
     },
     /// List tasks (pending by default)
     #[command(after_help = "Output format:
-  <id>  [status]  <title> [^parent] [@depend]
+  <id>  [status]  <title> [^parent] [@depend...] [!priority] [#label...] [<assignee>] [~due]
 
 Status symbols:
   [ ] pending
   [>] in progress
   [x] done
+  [-] cancelled
+Custom workflow states use the markers from .tsk/config.json (see tsk workflow).
 
 Examples:
-  tsk list                  # pending tasks only
-  tsk list --inprogress     # in progress tasks only
-  tsk list --all            # all tasks
-  tsk list --parent abc123  # only children of abc123")]
+  tsk list                   # pending tasks only
+  tsk list --inprogress      # in progress tasks only
+  tsk list --all             # all tasks
+  tsk list --status blocked  # tasks in a specific workflow state
+  tsk list --label bug       # pending tasks labelled bug
+  tsk list --overdue         # open tasks past their due date
+  tsk list --due-before +1w  # open tasks due within a week
+  tsk list --mine            # open tasks claimed by you (TSK_ACTOR or USER)
+  tsk list --parent abc123   # only children of abc123")]
     List {
         /// Show in progress tasks only
         #[arg(long)]
@@ -175,96 +790,600 @@ Examples:
         /// Filter by parent task ID
         #[arg(long)]
         parent: Option<String>,
+        /// Filter by workflow state name
+        #[arg(long)]
+        status: Option<String>,
+        /// Filter by label
+        #[arg(long)]
+        label: Option<String>,
+        /// Only open tasks past their due date
+        #[arg(long)]
+        overdue: bool,
+        /// Only tasks due before this date (YYYY-MM-DD, today, tomorrow, +3d, +2w)
+        #[arg(long)]
+        due_before: Option<String>,
+        /// Only open tasks claimed by you (TSK_ACTOR, else USER)
+        #[arg(long)]
+        mine: bool,
     },
-    /// Update task description by ID
-    #[command(after_help = "Example:
-  tsk update a1b2c3 \"New detailed description\"")]
+    /// Update task description and/or priority by ID
+    #[command(after_help = "Examples:
+  tsk update a1b2c3 \"New detailed description\"
+  tsk update a1b2c3 --priority 3")]
     Update {
         /// Task ID (6 chars, e.g., a1b2c3)
         id: String,
         /// New description text
-        description: String,
+        description: Option<String>,
+        /// New priority (higher is more urgent)
+        #[arg(long, allow_negative_numbers = true)]
+        priority: Option<i32>,
+    },
+    /// Edit task fields: title, description, priority, parent, dependencies, due date
+    #[command(after_help = "Only the given fields change. --depend replaces all dependencies
+(use 'tsk depend add/rm' to change one at a time).
+
+Examples:
+  tsk edit a1b2c3 --title \"Fix login bug\"
+  tsk edit a1b2c3 --parent x7y8z9
+  tsk edit a1b2c3 --no-parent
+  tsk edit a1b2c3 --depend k4l5m6 --depend p0q1r2
+  tsk edit a1b2c3 --no-depend
+  tsk edit a1b2c3 --due 2026-11-01
+  tsk edit a1b2c3 --no-due")]
+    Edit {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+        /// New title
+        #[arg(long)]
+        title: Option<String>,
+        /// New description
+        #[arg(long)]
+        description: Option<String>,
+        /// New priority (higher is more urgent)
+        #[arg(long, allow_negative_numbers = true)]
+        priority: Option<i32>,
+        /// New parent task ID
+        #[arg(long, conflicts_with = "no_parent")]
+        parent: Option<String>,
+        /// Remove the parent (make top-level)
+        #[arg(long)]
+        no_parent: bool,
+        /// Replace dependencies (repeatable)
+        #[arg(long, conflicts_with = "no_depend")]
+        depend: Vec<String>,
+        /// Remove all dependencies
+        #[arg(long)]
+        no_depend: bool,
+        /// New due date (YYYY-MM-DD, today, tomorrow, +3d, +2w)
+        #[arg(long, conflicts_with = "no_due")]
+        due: Option<String>,
+        /// Remove the due date
+        #[arg(long)]
+        no_due: bool,
+    },
+    /// Show the next actionable task
+    #[command(after_help = "Picks the highest-priority pending task whose dependencies are all done.
+Ties are broken by creation time (oldest first).")]
+    Next,
+    /// Claim a task: start it and assign it to you, atomically
+    #[command(after_help = "Without an ID, claims the next actionable task (see 'tsk next').
+The claim is a single conditional update, so two agents running it at the same
+time never get the same task.
+
+Examples:
+  tsk claim                  # claim the next ready task as $TSK_ACTOR / $USER
+  tsk claim --as agent-2     # claim the next ready task as agent-2
+  tsk claim a1b2c3 --as agent-2
+  tsk claim --as agent-2 --lease 30m")]
+    Claim {
+        /// Task ID (6 chars, e.g., a1b2c3); omit to claim the next ready task
+        id: Option<String>,
+        /// Agent name to assign (default: TSK_ACTOR, else USER)
+        #[arg(long = "as", value_name = "AGENT")]
+        agent: Option<String>,
+        /// Lease length (e.g. 90s, 30m, 2h, 1d); renew with 'tsk heartbeat'
+        #[arg(long)]
+        lease: Option<String>,
+    },
+    /// Show tasks as a parent/child tree
+    #[command(after_help = "Without a root, shows every top-level task and its subtasks.
+Done and cancelled tasks are hidden unless they still have open subtasks (use --all).
+
+Examples:
+  tsk tree                 # whole project
+  tsk tree a1b2c3          # one epic
+  tsk tree --depth 1       # top level and direct children only")]
+    Tree {
+        /// Root task ID (6 chars, e.g., a1b2c3)
+        root: Option<String>,
+        /// Maximum depth below the root (0 = root only)
+        #[arg(long)]
+        depth: Option<usize>,
+        /// Include done and cancelled tasks
+        #[arg(long)]
+        all: bool,
+    },
+    /// Show open tasks grouped by due date
+    #[command(after_help = "Lists pending and in-progress tasks that have a due date, one group per day,
+earliest first. Past days are marked overdue.")]
+    Agenda,
+    /// Show a compact project digest for starting an agent session
+    #[command(after_help = "Prints in-progress tasks with descriptions, ready tasks, pinned and relevant
+memories, and recent activity as markdown. Entries are added in order of
+importance until the token budget (about 4 characters per token) is used up.
+Pin memories with 'tsk m pin <id>' to always include them.
+
+Example:
+  tsk context --budget 1000")]
+    Context {
+        /// Approximate maximum size in tokens
+        #[arg(long, default_value_t = 2000)]
+        budget: usize,
+    },
+    /// Search tasks and memories
+    #[command(after_help = "Ranked full-text search over task titles, descriptions and memory content.
+Every word must match, as a prefix (\"auth\" finds \"authentication\"); matches
+are shown between ** in the snippet.
+
+Examples:
+  tsk search \"login bug\"
+  tsk search cache --kind memory
+  tsk search auth --status in_progress   # tasks only")]
+    Search {
+        /// Search words
+        query: String,
+        /// Only tasks or only memories
+        #[arg(long, value_enum)]
+        kind: Option<SearchKind>,
+        /// Only tasks in this workflow state
+        #[arg(long)]
+        status: Option<String>,
+        /// Maximum number of results
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
     },
     /// Start working on a task (mark as in progress)
-    #[command(after_help = "Sets task status from pending to in_progress.
-Task must be in pending status to start.")]
+    #[command(after_help = "Sets task status from pending to in_progress and assigns the task to the
+current actor (TSK_ACTOR, falling back to $USER), like 'tsk claim'.
+Task must be in pending status to start.
+
+With --lease, the task returns to pending if no 'tsk heartbeat' arrives
+before the lease runs out (e.g. the agent crashed).
+
+Examples:
+  tsk start a1b2c3
+  tsk start a1b2c3 --lease 30m")]
     Start {
         /// Task ID (6 chars, e.g., a1b2c3)
         id: String,
+        /// Lease length (e.g. 90s, 30m, 2h, 1d); renew with 'tsk heartbeat'
+        #[arg(long)]
+        lease: Option<String>,
+    },
+    /// Renew the lease on an in-progress task
+    #[command(after_help = "Extends the lease by its original length, or by --lease if given.
+Only the agent the task is assigned to can renew it; unassigned tasks must be
+claimed first.
+
+Examples:
+  tsk heartbeat a1b2c3
+  tsk heartbeat a1b2c3 --as agent-1 --lease 1h")]
+    Heartbeat {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+        /// Agent that holds the task (default: TSK_ACTOR, else USER)
+        #[arg(long = "as", value_name = "AGENT")]
+        agent: Option<String>,
+        /// New lease length (e.g. 90s, 30m, 2h, 1d)
+        #[arg(long)]
+        lease: Option<String>,
     },
     /// Mark task as done by ID
-    #[command(after_help = "Note: If task has --depend, the dependency must be completed first.")]
+    #[command(after_help = "Note: If task has dependencies, all of them must be completed first.")]
     Done {
         /// Task ID (6 chars, e.g., a1b2c3)
         id: String,
     },
+    /// Reopen a done or cancelled task (back to pending)
+    #[command(after_help = "Warns when tasks that depend on it were already completed.")]
+    Reopen {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+    },
+    /// Stop working on a task (in progress back to pending)
+    Stop {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+    },
+    /// Cancel a task
+    #[command(after_help = "Cancelled tasks don't satisfy dependencies: tasks that depend on it stay blocked.
+Use 'tsk reopen' to bring it back.")]
+    Cancel {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+    },
+    /// Move task to any workflow state
+    #[command(after_help = "Moves are checked against the workflow transitions.
+Moving to a done-kind state requires all dependencies to be done.
+
+Example:
+  tsk status a1b2c3 blocked")]
+    Status {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+        /// Workflow state name
+        state: String,
+    },
+    /// Show workflow states and transitions
+    #[command(after_help = "States are configured in .tsk/config.json, for example:
+
+  {
+    \"workflow\": {
+      \"states\": [
+        { \"name\": \"pending\",     \"marker\": \" \", \"kind\": \"pending\" },
+        { \"name\": \"blocked\",     \"marker\": \"!\", \"kind\": \"pending\" },
+        { \"name\": \"in_progress\", \"marker\": \">\", \"kind\": \"in_progress\" },
+        { \"name\": \"in_review\",   \"marker\": \"?\", \"kind\": \"in_progress\" },
+        { \"name\": \"done\",        \"marker\": \"x\", \"kind\": \"done\" },
+        { \"name\": \"cancelled\",   \"marker\": \"-\", \"kind\": \"cancelled\" }
+      ],
+      \"transitions\": {
+        \"pending\": [\"in_progress\", \"blocked\", \"cancelled\"],
+        \"blocked\": [\"pending\"],
+        \"in_progress\": [\"in_review\", \"blocked\"],
+        \"in_review\": [\"in_progress\", \"done\"]
+      }
+    }
+  }
+
+Kinds: pending (listed by default), in_progress (--inprogress), done,
+cancelled (closed, does not satisfy dependencies).
+New tasks get the first pending state, start moves to the first in_progress
+state, done to the first done state and cancel to the first cancelled state.
+Omit transitions to allow any move.")]
+    Workflow,
     /// Remove task by ID
     #[command(after_help = "Cannot remove tasks that:
-  - Have child tasks (--parent references this task)
-  - Have active dependents (--depend references this task)")]
+  - Have child tasks (--parent references this task), unless --recursive,
+    --orphan-children or --reparent-to says what to do with them
+  - Have active dependents (--depend references this task or, with
+    --recursive, any task in its subtree)
+
+--recursive asks before removing subtasks; pass --yes when not in a terminal.
+
+Examples:
+  tsk remove abc123 --recursive --dry-run   Preview what would be removed
+  tsk remove abc123 --recursive --yes       Remove the subtree without asking
+  tsk remove abc123 --reparent-to def456    Move subtasks under def456 first")]
     Remove {
         /// Task ID (6 chars, e.g., a1b2c3)
         id: String,
+        /// Remove the task and all of its subtasks
+        #[arg(long, conflicts_with_all = ["orphan_children", "reparent_to"])]
+        recursive: bool,
+        /// Make the subtasks top-level tasks
+        #[arg(long, conflicts_with = "reparent_to")]
+        orphan_children: bool,
+        /// Move the subtasks under this task
+        #[arg(long, value_name = "ID")]
+        reparent_to: Option<String>,
+        /// Show what would be removed without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't ask before removing subtasks with --recursive
+        #[arg(long, short)]
+        yes: bool,
     },
     /// Show full task details by ID
-    #[command(after_help = "Displays: ID, title, status, parent, dependency, created date, and full description.")]
+    #[command(after_help = "Displays: ID, title, status, parent, dependencies, created date, full description and comments.")]
     Show {
         /// Task ID (6 chars, e.g., a1b2c3)
         id: String,
     },
-    /// Generate shell completions
-    #[command(hide = true)]
-    Completions {
-        /// Shell type
-        shell: Shell,
-    },
-    /// List task IDs only (for shell completions)
-    #[command(hide = true)]
-    Ids,
-    /// Run MCP server for IDE integration
-    #[command(hide = true)]
-    Mcp,
-    /// Store project knowledge and notes (memory)
+    /// Manage task dependencies
     #[command(after_help = "Examples:
-  tsk m \"API uses JWT tokens\"              # quick create
-  tsk m \"Deploy via CI\" --tags deploy,ci   # with tags
-  tsk m list                                # show all
-  tsk m list --tag api                      # filter by tag
-  tsk m show abc123                         # full details
-  tsk m search \"JWT\"                        # search content
-  tsk m rm abc123                           # remove")]
-    M {
+  tsk depend add a1b2c3 x7y8z9   # a1b2c3 can't be done until x7y8z9 is done
+  tsk depend rm a1b2c3 x7y8z9    # drop that dependency")]
+    Depend {
         #[command(subcommand)]
-        action: Option<MemoryCommands>,
-        /// Quick create: content text
-        content: Option<String>,
-        /// Tags (comma-separated)
-        #[arg(long, short)]
-        tags: Option<String>,
+        action: DependCommands,
     },
-}
+    /// Manage task labels
+    #[command(after_help = "Labels are lowercased; they can't contain spaces or commas.
 
-#[derive(Subcommand)]
-enum MemoryCommands {
+Examples:
+  tsk label add a1b2c3 bug
+  tsk label rm a1b2c3 bug")]
+    Label {
+        #[command(subcommand)]
+        action: LabelCommands,
+    },
+    /// Show task history (event log)
+    #[command(after_help = "Every create, update, start, done and remove is recorded with
+a timestamp, old/new values and the actor (TSK_ACTOR, else $USER).
+
+Examples:
+  tsk log a1b2c3           # history of one task
+  tsk log --all            # history of all tasks
+  tsk log --all --last 20  # 20 most recent events")]
+    Log {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        id: Option<String>,
+        /// Show events for all tasks
+        #[arg(long)]
+        all: bool,
+        /// Show only last N events
+        #[arg(long)]
+        last: Option<usize>,
+    },
+    /// Undo the last N changes
+    #[command(after_help = "Reverses create, edit/update, start/claim, stop, done, reopen, cancel,
+status, remove, depend, label, comment and memory create/rm, newest first,
+by restoring the rows as they were before each change. The last 200
+changes are kept. Lease renewals and expiries are not recorded.
+
+Examples:
+  tsk undo             # undo the last change
+  tsk undo 3 --dry-run # show the last 3 changes without undoing them")]
+    Undo {
+        /// Number of changes to undo
+        #[arg(default_value_t = 1)]
+        count: usize,
+        /// Show what would be undone without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Add a comment (progress note) to a task
+    #[command(after_help = "Comments are kept in order and shown by 'tsk show', so progress
+notes don't overwrite the description.
+
+Example:
+  tsk comment a1b2c3 \"Login works, validation still missing\"")]
+    Comment {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+        /// Comment text
+        text: String,
+    },
+    /// List comments on a task (oldest first)
+    Comments {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+    },
+    /// Link a memory to a task
+    #[command(after_help = "Linked memories are shown by 'tsk show', and linked tasks by 'tsk m show'.
+Create and link in one go with: tsk m \"...\" --task <id>")]
+    Link {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        task: String,
+        /// Memory ID (6 chars)
+        memory: String,
+    },
+    /// Remove the link between a memory and a task
+    Unlink {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        task: String,
+        /// Memory ID (6 chars)
+        memory: String,
+    },
+    /// Check the task graph for integrity problems
+    #[command(after_help = "Detects:
+  - task IDs also used by a memory
+  - invalid status values
+  - orphaned parent references (^id points to a missing task)
+  - orphaned dependencies (@id points to a missing task)
+  - parent and dependency cycles
+
+Examples:
+  tsk doctor          # report problems
+  tsk doctor --fix    # repair them
+  tsk doctor --json   # machine-readable report")]
+    Doctor {
+        /// Repair problems (clear orphaned links, break cycles, reset invalid statuses)
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Generate shell completions
+    #[command(hide = true)]
+    Completions {
+        /// Shell type
+        shell: Shell,
+    },
+    /// List task IDs only (for shell completions)
+    #[command(hide = true)]
+    Ids,
+    /// Run MCP server for IDE integration
+    #[command(hide = true)]
+    Mcp,
+    /// Store project knowledge and notes (memory)
+    #[command(after_help = "Examples:
+  tsk m \"API uses JWT tokens\"              # quick create
+  tsk m \"Deploy via CI\" --tags deploy,ci   # with tags
+  tsk m \"Use bcrypt\" --task a1b2c3         # linked to a task
+  tsk m \"Use SQLite\" --kind decision       # typed: decision, convention, gotcha
+  tsk m list                                # show all
+  tsk m list --kind gotcha                  # only gotchas
+  tsk m \"Staging is on v2\" --expires +30d   # stale after 30 days
+  tsk m list --stale                        # due for review or expired
+  tsk m review                              # keep, edit or delete stale entries
+  tsk m list --tag api --tag auth           # entries tagged api and auth
+  tsk m list --tag api --tag auth --any     # tagged api or auth
+  tsk m tags                                # tags with counts
+  tsk m show abc123                         # full details
+  tsk m search \"JWT\"                        # search content
+  tsk m pin abc123                          # always include in 'tsk context'
+  tsk m export --adr docs/adr               # decisions as ADR files
+  tsk m rm abc123                           # remove")]
+    M {
+        #[command(subcommand)]
+        action: Option<MemoryCommands>,
+        /// Quick create: content text
+        content: Option<String>,
+        /// Tags (comma-separated)
+        #[arg(long, short, value_delimiter = ',')]
+        tags: Vec<String>,
+        /// Link the new entry to a task (repeatable)
+        #[arg(long)]
+        task: Vec<String>,
+        /// Kind of entry
+        #[arg(long, value_enum)]
+        kind: Option<MemoryKind>,
+        /// Check the entry again from this date (YYYY-MM-DD, today, tomorrow, +3d, +2w)
+        #[arg(long)]
+        review_after: Option<String>,
+        /// The entry no longer holds from this date (YYYY-MM-DD, +3d, ...)
+        #[arg(long)]
+        expires: Option<String>,
+        /// Create even if a similar entry exists and the config refuses duplicates
+        #[arg(long, conflicts_with = "unique")]
+        force: bool,
+        /// Refuse if a similar entry exists
+        #[arg(long)]
+        unique: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DependCommands {
+    /// Add a dependency to a task
+    Add {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+        /// Dependency task ID
+        depend: String,
+    },
+    /// Remove a dependency from a task
+    Rm {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+        /// Dependency task ID
+        depend: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum LabelCommands {
+    /// Add a label to a task
+    Add {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+        /// Label name
+        label: String,
+    },
+    /// Remove a label from a task
+    Rm {
+        /// Task ID (6 chars, e.g., a1b2c3)
+        id: String,
+        /// Label name
+        label: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum MemoryCommands {
     /// List all memory entries
     List {
-        /// Filter by tag
+        /// Only entries with this tag (repeatable; all must match)
         #[arg(long)]
-        tag: Option<String>,
+        tag: Vec<String>,
+        /// Match entries with any of the tags instead of all
+        #[arg(long, requires = "tag")]
+        any: bool,
+        /// Only entries of this kind
+        #[arg(long, value_enum)]
+        kind: Option<MemoryKind>,
+        /// Only entries whose review or expiry date has come
+        #[arg(long)]
+        stale: bool,
         /// Show only last N entries
         #[arg(long)]
         last: Option<usize>,
     },
+    /// Edit a memory entry in place (keeps its ID)
+    #[command(after_help = "Without any options in a terminal, opens the content in $VISUAL/$EDITOR.
+
+Examples:
+  tsk m edit abc123 --content \"Deploys go through GitHub Actions\"
+  tsk m edit abc123 --add-tag ci --rm-tag jenkins
+  tsk m edit abc123                 # edit content in $EDITOR")]
+    Edit {
+        /// Memory ID (6 chars)
+        id: String,
+        /// New content
+        #[arg(long)]
+        content: Option<String>,
+        /// New kind
+        #[arg(long, value_enum, conflicts_with = "no_kind")]
+        kind: Option<MemoryKind>,
+        /// Make the entry a plain note again
+        #[arg(long)]
+        no_kind: bool,
+        /// New review date (YYYY-MM-DD, today, tomorrow, +3d, +2w)
+        #[arg(long, conflicts_with = "no_review_after")]
+        review_after: Option<String>,
+        /// Remove the review date
+        #[arg(long)]
+        no_review_after: bool,
+        /// New expiry date (YYYY-MM-DD, today, tomorrow, +3d, +2w)
+        #[arg(long, conflicts_with = "no_expires")]
+        expires: Option<String>,
+        /// Remove the expiry date
+        #[arg(long)]
+        no_expires: bool,
+        /// Replace all tags (comma-separated; empty clears them)
+        #[arg(long, value_delimiter = ',', num_args = 0..=1,
+              conflicts_with_all = ["add_tag", "rm_tag"])]
+        tags: Option<Vec<String>>,
+        /// Add a tag (repeatable)
+        #[arg(long)]
+        add_tag: Vec<String>,
+        /// Remove a tag (repeatable)
+        #[arg(long)]
+        rm_tag: Vec<String>,
+    },
     /// Show full memory entry
     Show {
         /// Memory ID (6 chars)
         id: String,
     },
+    /// List tags with the number of entries using each
+    Tags,
+    /// Walk through stale entries: keep, edit or delete each one
+    #[command(after_help = "An entry is stale once its --review-after or --expires date has come.
+Keeping or editing an entry sets its next review date; keeping an expired
+entry also removes the expiry date. Needs a terminal; agents can use
+'tsk m list --stale' instead.")]
+    Review,
     /// Search memories by content
     Search {
         /// Search query
         query: String,
     },
+    /// Pin a memory entry so it is always included in 'tsk context'
+    Pin {
+        /// Memory ID (6 chars)
+        id: String,
+    },
+    /// Unpin a memory entry
+    Unpin {
+        /// Memory ID (6 chars)
+        id: String,
+    },
+    /// Export memories to files
+    #[command(after_help = "Decisions are written as numbered Architecture Decision Records
+(0001-<title>.md); the first line of each entry is the title. Re-exports
+update earlier files in place: a decision keeps its number, and new decisions
+are numbered after the highest ADR in the directory.
+
+Example:
+  tsk m export --adr docs/adr")]
+    Export {
+        /// Write decisions as ADR markdown files into this directory
+        #[arg(long, value_name = "DIR")]
+        adr: PathBuf,
+    },
     /// Remove memory entry
     Rm {
         /// Memory ID (6 chars)
@@ -282,6 +1401,44 @@ fn find_db_path() -> Option<PathBuf> {
     }
 }
 
+fn validate_state_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+        bail!("Invalid state name '{}'. Use [a-z0-9_].", name);
+    }
+    Ok(())
+}
+
+fn load_config() -> Result<ProjectConfig> {
+    let current_dir = env::current_dir().context("Failed to get current directory")?;
+    let config_path = current_dir.join(".tsk").join("config.json");
+    if !config_path.exists() {
+        return Ok(ProjectConfig::default());
+    }
+
+    let text = fs::read_to_string(&config_path)
+        .with_context(|| format!("Failed to read {}", config_path.display()))?;
+    let config: ProjectConfig = serde_json::from_str(&text)
+        .with_context(|| format!("Invalid config in {}", config_path.display()))?;
+    config.workflow.validate()?;
+    Ok(config)
+}
+
+static CONFIG: OnceLock<ProjectConfig> = OnceLock::new();
+
+/// Project configuration, loaded once from .tsk/config.json
+pub fn project_config() -> Result<&'static ProjectConfig> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+    let config = load_config()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// Task workflow from project config
+pub fn workflow() -> Result<&'static Workflow> {
+    Ok(&project_config()?.workflow)
+}
+
 fn id_exists_in_table(conn: &Connection, table: &str, id: &str) -> Result<bool> {
     let query = format!("SELECT COUNT(*) FROM {} WHERE id = ?1", table);
     let count: i32 = conn.query_row(&query, [id], |row| row.get(0))?;
@@ -300,7 +1457,9 @@ fn generate_id(conn: &Connection, table: &str) -> Result<String> {
             })
             .collect();
 
-        if !id_exists_in_table(conn, table, &id)? {
+        // Tasks and memories share one ID space
+        let other = if table == "tasks" { "memories" } else { "tasks" };
+        if !id_exists_in_table(conn, table, &id)? && !id_exists_in_table(conn, other, &id)? {
             return Ok(id);
         }
     }
@@ -315,6 +1474,50 @@ fn validate_id(id: &str) -> Result<()> {
     Ok(())
 }
 
+/// Full-text indexes over task titles/descriptions and memory content,
+/// kept in sync with their tables by triggers
+const SEARCH_SCHEMA: &str = "
+    CREATE VIRTUAL TABLE IF NOT EXISTS tasks_fts USING fts5(
+        id UNINDEXED, title, description, tokenize='porter unicode61'
+    );
+    CREATE TRIGGER IF NOT EXISTS tasks_fts_insert AFTER INSERT ON tasks BEGIN
+        INSERT INTO tasks_fts (id, title, description)
+        VALUES (new.id, new.title, new.description);
+    END;
+    CREATE TRIGGER IF NOT EXISTS tasks_fts_delete AFTER DELETE ON tasks BEGIN
+        DELETE FROM tasks_fts WHERE id = old.id;
+    END;
+    CREATE TRIGGER IF NOT EXISTS tasks_fts_update AFTER UPDATE OF id, title, description ON tasks BEGIN
+        DELETE FROM tasks_fts WHERE id = old.id;
+        INSERT INTO tasks_fts (id, title, description)
+        VALUES (new.id, new.title, new.description);
+    END;
+    CREATE VIRTUAL TABLE IF NOT EXISTS memories_fts USING fts5(
+        id UNINDEXED, content, tokenize='porter unicode61'
+    );
+    CREATE TRIGGER IF NOT EXISTS memories_fts_insert AFTER INSERT ON memories BEGIN
+        INSERT INTO memories_fts (id, content) VALUES (new.id, new.content);
+    END;
+    CREATE TRIGGER IF NOT EXISTS memories_fts_delete AFTER DELETE ON memories BEGIN
+        DELETE FROM memories_fts WHERE id = old.id;
+    END;
+    CREATE TRIGGER IF NOT EXISTS memories_fts_update AFTER UPDATE OF id, content ON memories BEGIN
+        DELETE FROM memories_fts WHERE id = old.id;
+        INSERT INTO memories_fts (id, content) VALUES (new.id, new.content);
+    END;
+";
+
+/// Re-index every task and memory for full-text search
+fn rebuild_search(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "DELETE FROM tasks_fts;
+         INSERT INTO tasks_fts (id, title, description) SELECT id, title, description FROM tasks;
+         DELETE FROM memories_fts;
+         INSERT INTO memories_fts (id, content) SELECT id, content FROM memories;",
+    )?;
+    Ok(())
+}
+
 fn init_db(conn: &Connection) -> Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS tasks (
@@ -324,6 +1527,13 @@ fn init_db(conn: &Connection) -> Result<()> {
             done INTEGER DEFAULT 0,
             parent_id TEXT,
             depend_id TEXT,
+            priority INTEGER DEFAULT 0,
+            status TEXT,
+            due TEXT,
+            assignee TEXT,
+            started_at TEXT,
+            lease_until TEXT,
+            lease_seconds INTEGER,
             created_at TEXT DEFAULT CURRENT_TIMESTAMP
         )",
         [],
@@ -339,11 +1549,108 @@ fn init_db(conn: &Connection) -> Result<()> {
         "CREATE TABLE IF NOT EXISTS memories (
             id TEXT PRIMARY KEY,
             content TEXT NOT NULL,
-            tags TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT,
+            kind TEXT,
+            pinned INTEGER NOT NULL DEFAULT 0,
+            review_after TEXT,
+            expires_at TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS memory_tags (
+            memory_id TEXT NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (memory_id, tag)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS task_memories (
+            task_id TEXT NOT NULL,
+            memory_id TEXT NOT NULL,
+            PRIMARY KEY (task_id, memory_id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS task_dependencies (
+            task_id TEXT NOT NULL,
+            depend_id TEXT NOT NULL,
+            PRIMARY KEY (task_id, depend_id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS task_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_id TEXT NOT NULL,
+            event TEXT NOT NULL,
+            field TEXT,
+            old_value TEXT,
+            new_value TEXT,
+            actor TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS task_comments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_id TEXT NOT NULL,
+            text TEXT NOT NULL,
+            author TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS task_labels (
+            task_id TEXT NOT NULL,
+            label TEXT NOT NULL,
+            PRIMARY KEY (task_id, label)
+        )",
+        [],
+    )?;
+    // Undo journal: one entry per mutating operation, with the before-images
+    // of the rows it touched (a JSON array per table and key) and their
+    // after-images, so undo can tell whether something else changed them
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS undo_journal (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            operation TEXT NOT NULL,
+            actor TEXT NOT NULL,
             created_at TEXT DEFAULT CURRENT_TIMESTAMP
         )",
         [],
     )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS undo_rows (
+            journal_id INTEGER NOT NULL,
+            table_name TEXT NOT NULL,
+            key_column TEXT NOT NULL,
+            key TEXT NOT NULL,
+            rows TEXT NOT NULL,
+            after_rows TEXT
+        )",
+        [],
+    )?;
+    conn.execute_batch(SEARCH_SCHEMA)?;
+    Ok(())
+}
+
+/// Add a column unless the table already has it (databases created by
+/// `init_db` start with the full schema but no schema version)
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|r| r.ok())
+        .any(|c| c == column);
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
     Ok(())
 }
 
@@ -370,6 +1677,27 @@ fn migrate_db(conn: &Connection) -> Result<()> {
     if !columns.contains(&"depend_id".to_string()) {
         conn.execute("ALTER TABLE tasks ADD COLUMN depend_id TEXT", [])?;
     }
+    if !columns.contains(&"priority".to_string()) {
+        conn.execute("ALTER TABLE tasks ADD COLUMN priority INTEGER DEFAULT 0", [])?;
+    }
+    if !columns.contains(&"status".to_string()) {
+        conn.execute("ALTER TABLE tasks ADD COLUMN status TEXT", [])?;
+    }
+    if !columns.contains(&"due".to_string()) {
+        conn.execute("ALTER TABLE tasks ADD COLUMN due TEXT", [])?;
+    }
+    if !columns.contains(&"assignee".to_string()) {
+        conn.execute("ALTER TABLE tasks ADD COLUMN assignee TEXT", [])?;
+    }
+    if !columns.contains(&"started_at".to_string()) {
+        conn.execute("ALTER TABLE tasks ADD COLUMN started_at TEXT", [])?;
+    }
+    if !columns.contains(&"lease_until".to_string()) {
+        conn.execute("ALTER TABLE tasks ADD COLUMN lease_until TEXT", [])?;
+    }
+    if !columns.contains(&"lease_seconds".to_string()) {
+        conn.execute("ALTER TABLE tasks ADD COLUMN lease_seconds INTEGER", [])?;
+    }
 
     // Get current schema version
     let schema_version: i32 = conn
@@ -406,279 +1734,2481 @@ fn migrate_db(conn: &Connection) -> Result<()> {
         )?;
     }
 
-    Ok(())
-}
-
-fn task_exists(conn: &Connection, id: &str) -> Result<bool> {
-    let count: i32 = conn.query_row(
-        "SELECT COUNT(*) FROM tasks WHERE id = ?1",
-        [id],
-        |row| row.get(0),
-    )?;
-    Ok(count > 0)
-}
-
-fn task_is_done(conn: &Connection, id: &str) -> Result<Option<bool>> {
-    let result = conn.query_row(
-        "SELECT done FROM tasks WHERE id = ?1",
-        [id],
-        |row| row.get::<_, i32>(0),
-    );
-
-    match result {
-        Ok(done) => Ok(Some(done == 2)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.into()),
+    // Migration v3: single tasks.depend_id → task_dependencies join table
+    if schema_version < 3 {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS task_dependencies (
+                task_id TEXT NOT NULL,
+                depend_id TEXT NOT NULL,
+                PRIMARY KEY (task_id, depend_id)
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO task_dependencies (task_id, depend_id)
+             SELECT id, depend_id FROM tasks WHERE depend_id IS NOT NULL",
+            [],
+        )?;
+        conn.execute("UPDATE tasks SET depend_id = NULL", [])?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '3')",
+            [],
+        )?;
     }
-}
 
-// ============================================================================
-// Core functions (return data, used by CLI and MCP)
-// ============================================================================
+    // Migration v4: task event log
+    if schema_version < 4 {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS task_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id TEXT NOT NULL,
+                event TEXT NOT NULL,
+                field TEXT,
+                old_value TEXT,
+                new_value TEXT,
+                actor TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '4')",
+            [],
+        )?;
+    }
 
-/// Create a new task and return its ID
-pub fn create_task(
-    conn: &Connection,
-    title: &str,
-    description: &str,
-    parent: Option<&str>,
-    depend: Option<&str>,
-) -> Result<String> {
-    if let Some(parent_id) = parent {
-        validate_id(parent_id)?;
-        if !task_exists(conn, parent_id)? {
-            bail!("Parent task '{}' not found.", parent_id);
-        }
+    // Migration v5: workflow state names (tasks.done keeps the state kind)
+    if schema_version < 5 {
+        conn.execute(
+            "UPDATE tasks SET status = CASE done WHEN 0 THEN 'pending' WHEN 1 THEN 'in_progress' ELSE 'done' END
+             WHERE status IS NULL",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '5')",
+            [],
+        )?;
     }
 
-    if let Some(depend_id) = depend {
-        validate_id(depend_id)?;
-        if !task_exists(conn, depend_id)? {
-            bail!("Dependency task '{}' not found.", depend_id);
-        }
+    // Migration v6: task comments
+    if schema_version < 6 {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS task_comments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id TEXT NOT NULL,
+                text TEXT NOT NULL,
+                author TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '6')",
+            [],
+        )?;
     }
 
-    let id = generate_id(conn, "tasks")?;
-    conn.execute(
-        "INSERT INTO tasks (id, title, description, parent_id, depend_id) VALUES (?1, ?2, ?3, ?4, ?5)",
-        rusqlite::params![id, title, description, parent, depend],
-    )?;
-    Ok(id)
+    // Migration v7: task labels
+    if schema_version < 7 {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS task_labels (
+                task_id TEXT NOT NULL,
+                label TEXT NOT NULL,
+                PRIMARY KEY (task_id, label)
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '7')",
+            [],
+        )?;
+    }
+
+    // Migration v8: undo journal
+    if schema_version < 8 {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS undo_journal (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                operation TEXT NOT NULL,
+                actor TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS undo_rows (
+                journal_id INTEGER NOT NULL,
+                table_name TEXT NOT NULL,
+                key_column TEXT NOT NULL,
+                key TEXT NOT NULL,
+                rows TEXT NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '8')",
+            [],
+        )?;
+    }
+
+    // Migration v9: full-text search, indexing existing rows
+    if schema_version < 9 {
+        conn.execute_batch(SEARCH_SCHEMA)?;
+        rebuild_search(conn)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '9')",
+            [],
+        )?;
+    }
+
+    // Migration v10: memories.tags comma string → memory_tags table
+    if schema_version < 10 {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS memory_tags (
+                memory_id TEXT NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (memory_id, tag)
+            )",
+            [],
+        )?;
+
+        let mut stmt = conn.prepare("PRAGMA table_info(memories)")?;
+        let has_tags = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(|r| r.ok())
+            .any(|c| c == "tags");
+        drop(stmt);
+
+        if has_tags {
+            let mut stmt = conn.prepare("SELECT id, tags FROM memories WHERE tags IS NOT NULL")?;
+            let rows: Vec<(String, String)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<_>>()?;
+            drop(stmt);
+            for (id, tags) in rows {
+                for tag in tags.split(',') {
+                    // Old tags were free-form: "API Design" becomes "api-design"
+                    if let Ok(tag) = normalize_tag(tag) {
+                        conn.execute(
+                            "INSERT OR IGNORE INTO memory_tags (memory_id, tag) VALUES (?1, ?2)",
+                            [&id, &tag],
+                        )?;
+                    }
+                }
+            }
+            conn.execute("ALTER TABLE memories DROP COLUMN tags", [])?;
+            rebuild_search(conn)?;
+        }
+
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '10')",
+            [],
+        )?;
+    }
+
+    // Migration v11: task <-> memory links
+    if schema_version < 11 {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS task_memories (
+                task_id TEXT NOT NULL,
+                memory_id TEXT NOT NULL,
+                PRIMARY KEY (task_id, memory_id)
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '11')",
+            [],
+        )?;
+    }
+
+    // Migration v12: memory edit times
+    if schema_version < 12 {
+        add_column(conn, "memories", "updated_at", "TEXT")?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '12')",
+            [],
+        )?;
+    }
+
+    // Migration v13: memory kinds
+    if schema_version < 13 {
+        add_column(conn, "memories", "kind", "TEXT")?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '13')",
+            [],
+        )?;
+    }
+
+    // Migration v14: pinned memories
+    if schema_version < 14 {
+        add_column(conn, "memories", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '14')",
+            [],
+        )?;
+    }
+
+    // Migration v15: memory review and expiry dates
+    if schema_version < 15 {
+        add_column(conn, "memories", "review_after", "TEXT")?;
+        add_column(conn, "memories", "expires_at", "TEXT")?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '15')",
+            [],
+        )?;
+    }
+
+    // Migration v16: undo after-images (older entries have none and aren't checked)
+    if schema_version < 16 {
+        add_column(conn, "undo_rows", "after_rows", "TEXT")?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '16')",
+            [],
+        )?;
+    }
+
+    // Migration v17: search indexes keyed by ID. The v9 indexes pointed at
+    // rowids, which VACUUM may renumber on tables with a TEXT primary key.
+    if schema_version < 17 {
+        conn.execute_batch(
+            "DROP TRIGGER IF EXISTS tasks_fts_insert;
+             DROP TRIGGER IF EXISTS tasks_fts_delete;
+             DROP TRIGGER IF EXISTS tasks_fts_update;
+             DROP TRIGGER IF EXISTS memories_fts_insert;
+             DROP TRIGGER IF EXISTS memories_fts_delete;
+             DROP TRIGGER IF EXISTS memories_fts_update;
+             DROP TABLE IF EXISTS tasks_fts;
+             DROP TABLE IF EXISTS memories_fts;",
+        )?;
+        conn.execute_batch(SEARCH_SCHEMA)?;
+        rebuild_search(conn)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', '17')",
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn task_exists(conn: &Connection, id: &str) -> Result<bool> {
+    let count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM tasks WHERE id = ?1",
+        [id],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn task_is_done(conn: &Connection, id: &str) -> Result<Option<bool>> {
+    let result = conn.query_row(
+        "SELECT done FROM tasks WHERE id = ?1",
+        [id],
+        |row| row.get::<_, i32>(0),
+    );
+
+    match result {
+        Ok(done) => Ok(Some(done == 2)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Current workflow state name of a task
+fn task_state(conn: &Connection, id: &str) -> Result<String> {
+    conn.query_row("SELECT status FROM tasks WHERE id = ?1", [id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => anyhow::anyhow!("Task '{}' not found.", id),
+            _ => e.into(),
+        })
+}
+
+fn task_assignee(conn: &Connection, id: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row("SELECT assignee FROM tasks WHERE id = ?1", [id], |row| row.get(0))
+        .optional()?
+        .flatten())
+}
+
+/// Move a task from state `from` to `to` on the caller's transaction,
+/// journaling its before-image and recording the event.
+/// The update only applies if the task is still in `from`, so concurrent
+/// transitions can't both succeed. Returning to pending releases the assignee;
+/// leaving in progress drops the lease.
+fn set_task_state(
+    conn: &Connection,
+    journal: &Journal,
+    id: &str,
+    from: &str,
+    to: &WorkflowState,
+    event: &str,
+) -> Result<()> {
+    journal.task(conn, id)?;
+    let updated = conn.execute(
+        "UPDATE tasks SET status = ?1, done = ?2,
+                assignee = CASE WHEN ?2 = 0 THEN NULL ELSE assignee END,
+                lease_until = CASE WHEN ?2 = 1 THEN lease_until END,
+                lease_seconds = CASE WHEN ?2 = 1 THEN lease_seconds END
+         WHERE id = ?3 AND status = ?4",
+        rusqlite::params![to.name, to.kind.to_int(), id, from],
+    )?;
+    if updated == 0 {
+        bail!("Task '{}' was changed by someone else; try again.", id);
+    }
+    log_event(conn, id, event, Some("status"), Some(from), Some(&to.name))?;
+    Ok(())
+}
+
+/// Fail unless all (existing) dependencies of a task are done
+fn ensure_dependencies_done(conn: &Connection, id: &str) -> Result<()> {
+    let blocking = unfinished_dependencies(conn, id)?;
+    match blocking.len() {
+        0 => Ok(()),
+        1 => bail!("Cannot complete: depends on '{}' which is not done.", blocking[0]),
+        _ => bail!(
+            "Cannot complete: depends on '{}' which are not done.",
+            blocking.join("', '")
+        ),
+    }
+}
+
+fn task_dependencies(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT depend_id FROM task_dependencies WHERE task_id = ?1 ORDER BY depend_id",
+    )?;
+    let ids = stmt.query_map([id], |row| row.get::<_, String>(0))?;
+
+    let mut result = Vec::new();
+    for id in ids {
+        result.push(id?);
+    }
+    Ok(result)
+}
+
+/// Dependencies of a task that exist but are not done yet
+fn unfinished_dependencies(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    let mut blocking = Vec::new();
+    for did in task_dependencies(conn, id)? {
+        match task_is_done(conn, &did)? {
+            Some(true) => {}
+            Some(false) => blocking.push(did),
+            None => {}
+        }
+    }
+    Ok(blocking)
+}
+
+/// Split the comma-joined `depends` column produced by list queries
+fn split_list(list: Option<String>) -> Vec<String> {
+    let mut items: Vec<String> = list
+        .map(|d| d.split(',').map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+    items.sort();
+    items
+}
+
+/// Lowercase and validate a label name
+fn normalize_label(label: &str) -> Result<String> {
+    let label = label.trim().to_lowercase();
+    if label.is_empty() {
+        bail!("Label cannot be empty.");
+    }
+    if label.contains(',') || label.chars().any(char::is_whitespace) {
+        bail!("Invalid label '{}'. Labels can't contain spaces or commas.", label);
+    }
+    Ok(label)
+}
+
+/// Lowercase and validate a memory tag, joining words with hyphens
+/// ("API Design" becomes "api-design", as migration v10 did for old tags)
+fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.split_whitespace().collect::<Vec<_>>().join("-").to_lowercase();
+    if tag.is_empty() {
+        bail!("Tag cannot be empty.");
+    }
+    if tag.contains(',') {
+        bail!("Invalid tag '{}'. Tags can't contain commas.", tag);
+    }
+    Ok(tag)
+}
+
+/// Furthest `+N[d|w]` offset accepted (about ten years)
+const MAX_DUE_OFFSET_DAYS: i64 = 3650;
+
+/// Parse a due date into YYYY-MM-DD. Accepts a date, `today`, `tomorrow`
+/// or an offset from today like `+3d` / `+2w`.
+fn parse_due(conn: &Connection, input: &str) -> Result<String> {
+    let input = input.trim().to_lowercase();
+    let invalid = || anyhow::anyhow!("Invalid date '{}'. Use YYYY-MM-DD, today, tomorrow, +3d or +2w.", input);
+
+    let offset_days = match input.as_str() {
+        "today" => Some(0),
+        "tomorrow" => Some(1),
+        s if s.starts_with('+') => {
+            let offset = &s[1..];
+            let (n, unit) = offset
+                .split_at_checked(offset.len().saturating_sub(1))
+                .ok_or_else(invalid)?;
+            if n.starts_with(['-', '+']) {
+                bail!("Invalid date '{}'. Offsets count forward from today, e.g. +3d.", input);
+            }
+            let n: i64 = n.parse().map_err(|_| invalid())?;
+            let days = match unit {
+                "d" => Some(n),
+                "w" => n.checked_mul(7),
+                _ => return Err(invalid()),
+            };
+            match days {
+                Some(days) if days <= MAX_DUE_OFFSET_DAYS => Some(days),
+                _ => bail!("Offset '{}' is too far ahead; the maximum is 3650d.", input),
+            }
+        }
+        _ => None,
+    };
+
+    let date: Option<String> = match offset_days {
+        Some(days) => conn.query_row(
+            "SELECT date('now', 'localtime', ?1)",
+            [format!("+{} days", days)],
+            |row| row.get(0),
+        )?,
+        // date() normalises out-of-range days, so require a round trip
+        None => conn
+            .query_row("SELECT date(?1)", [&input], |row| row.get::<_, Option<String>>(0))?
+            .filter(|d| *d == input),
+    };
+    date.ok_or_else(invalid)
+}
+
+/// Longest lease accepted, so lease dates stay well within SQLite's range
+const MAX_LEASE_SECONDS: i64 = 30 * 86400;
+
+/// Parse a lease length like `90s`, `30m`, `2h` or `1d` into seconds
+fn parse_lease(input: &str) -> Result<i64> {
+    let input = input.trim();
+    let invalid = || anyhow::anyhow!("Invalid lease '{}'. Use e.g. 90s, 30m, 2h or 1d.", input);
+
+    let (n, unit) = input
+        .split_at_checked(input.len().saturating_sub(1))
+        .ok_or_else(invalid)?;
+    let n: i64 = n.parse().map_err(|_| invalid())?;
+    let factor = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(invalid()),
+    };
+    let seconds = n.checked_mul(factor).ok_or_else(invalid)?;
+    if seconds <= 0 {
+        return Err(invalid());
+    }
+    if seconds > MAX_LEASE_SECONDS {
+        bail!("Lease '{}' is too long; the maximum is 30d.", input);
+    }
+    Ok(seconds)
+}
+
+/// Return in-progress tasks whose lease ran out to pending, so they can be
+/// claimed again. Runs on the caller's connection/transaction.
+fn release_expired_leases(conn: &Connection) -> Result<Vec<String>> {
+    let initial = workflow()?.first_of(TaskStatus::Pending);
+    let mut stmt = conn.prepare(
+        "SELECT id, status FROM tasks WHERE done = 1 AND lease_until < datetime('now')",
+    )?;
+    let expired: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    for (id, status) in &expired {
+        conn.execute(
+            "UPDATE tasks SET status = ?1, done = ?2, assignee = NULL, lease_until = NULL, lease_seconds = NULL
+             WHERE id = ?3",
+            rusqlite::params![initial.name, initial.kind.to_int(), id],
+        )?;
+        log_event(conn, id, "expired", Some("status"), Some(status), Some(&initial.name))?;
+    }
+    Ok(expired.into_iter().map(|(id, _)| id).collect())
+}
+
+fn task_labels(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT label FROM task_labels WHERE task_id = ?1 ORDER BY label")?;
+    let labels = stmt.query_map([id], |row| row.get::<_, String>(0))?;
+    Ok(labels.collect::<rusqlite::Result<_>>()?)
+}
+
+static ACTOR: OnceLock<String> = OnceLock::new();
+
+/// Set the actor recorded in the event log (used by the MCP server)
+pub fn set_actor(actor: &str) {
+    let _ = ACTOR.set(actor.to_string());
+}
+
+/// Who is making changes: explicit actor, else TSK_ACTOR, else $USER
+pub fn current_actor() -> String {
+    if let Some(actor) = ACTOR.get() {
+        return actor.clone();
+    }
+    env::var("TSK_ACTOR")
+        .or_else(|_| env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Append an entry to the task event log
+fn log_event(
+    conn: &Connection,
+    task_id: &str,
+    event: &str,
+    field: Option<&str>,
+    old_value: Option<&str>,
+    new_value: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO task_events (task_id, event, field, old_value, new_value, actor) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![task_id, event, field, old_value, new_value, current_actor()],
+    )?;
+    Ok(())
+}
+
+/// Rows that make up a task, as (table, key column) pairs saved for undo
+const TASK_ROWS: &[(&str, &str)] = &[
+    ("tasks", "id"),
+    ("task_dependencies", "task_id"),
+    ("task_dependencies", "depend_id"),
+    ("task_labels", "task_id"),
+    ("task_comments", "task_id"),
+    ("task_memories", "task_id"),
+];
+
+/// Rows that make up a memory entry, saved for undo
+const MEMORY_ROWS: &[(&str, &str)] = &[
+    ("memories", "id"),
+    ("memory_tags", "memory_id"),
+    ("task_memories", "memory_id"),
+];
+
+/// Keep this many operations in the undo journal
+const UNDO_HISTORY: i64 = 200;
+
+/// Undo journal entry being recorded on the caller's transaction.
+/// Save the before-image of everything an operation touches before changing it,
+/// then finish with `commit`, which records the after-images.
+struct Journal(i64);
+
+impl Journal {
+    /// Start an entry for `operation` (e.g. "remove abc123")
+    fn begin(conn: &Connection, operation: &str) -> Result<Journal> {
+        conn.execute(
+            "INSERT INTO undo_journal (operation, actor) VALUES (?1, ?2)",
+            rusqlite::params![operation, current_actor()],
+        )?;
+        let id = conn.last_insert_rowid();
+        conn.execute(
+            "DELETE FROM undo_rows WHERE journal_id <= ?1",
+            [id - UNDO_HISTORY],
+        )?;
+        conn.execute("DELETE FROM undo_journal WHERE id <= ?1", [id - UNDO_HISTORY])?;
+        Ok(Journal(id))
+    }
+
+    /// Save a task's row, dependencies (both ways), labels, comments and memory links
+    fn task(&self, conn: &Connection, id: &str) -> Result<()> {
+        self.save(conn, TASK_ROWS, id)
+    }
+
+    fn memory(&self, conn: &Connection, id: &str) -> Result<()> {
+        self.save(conn, MEMORY_ROWS, id)
+    }
+
+    /// Save each (table, key column) image once per entry: the first one is the before-image
+    fn save(&self, conn: &Connection, rows: &[(&str, &str)], key: &str) -> Result<()> {
+        for &(table, column) in rows {
+            let saved: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM undo_rows
+                 WHERE journal_id = ?1 AND table_name = ?2 AND key_column = ?3 AND key = ?4)",
+                rusqlite::params![self.0, table, column, key],
+                |row| row.get(0),
+            )?;
+            if saved {
+                continue;
+            }
+
+            let image = rows_image(conn, table, column, key)?;
+            conn.execute(
+                "INSERT INTO undo_rows (journal_id, table_name, key_column, key, rows)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![self.0, table, column, key, image],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Save the after-images of everything saved so far and commit
+    fn commit(self, tx: rusqlite::Transaction) -> Result<()> {
+        let mut stmt = tx.prepare(
+            "SELECT rowid, table_name, key_column, key FROM undo_rows WHERE journal_id = ?1",
+        )?;
+        let saved: Vec<(i64, String, String, String)> = stmt
+            .query_map([self.0], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        drop(stmt);
+
+        for (rowid, table, column, key) in saved {
+            tx.execute(
+                "UPDATE undo_rows SET after_rows = ?1 WHERE rowid = ?2",
+                rusqlite::params![rows_image(&tx, &table, &column, &key)?, rowid],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// The rows of `table` whose `column` is `key`, as a JSON array of objects.
+/// The rowid is kept so undo can restore rows in their original order.
+fn rows_image(conn: &Connection, table: &str, column: &str, key: &str) -> Result<String> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT rowid AS rowid, * FROM {} WHERE {} = ?1 ORDER BY rowid",
+        table, column
+    ))?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let image = stmt
+        .query_map([key], |row| {
+            let mut object = serde_json::Map::new();
+            for (i, name) in columns.iter().enumerate() {
+                object.insert(name.clone(), sql_to_json(row.get(i)?));
+            }
+            Ok(serde_json::Value::Object(object))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(serde_json::to_string(&image)?)
+}
+
+/// Whether two row images hold the same rows, ignoring rowids and order
+fn same_rows(a: &str, b: &str) -> Result<bool> {
+    let normalise = |image: &str| -> Result<Vec<String>> {
+        let rows: Vec<serde_json::Map<String, serde_json::Value>> = serde_json::from_str(image)?;
+        let mut rows: Vec<String> = rows
+            .into_iter()
+            .map(|mut row| {
+                row.remove("rowid");
+                serde_json::Value::Object(row).to_string()
+            })
+            .collect();
+        rows.sort();
+        Ok(rows)
+    };
+    Ok(normalise(a)? == normalise(b)?)
+}
+
+fn sql_to_json(value: rusqlite::types::Value) -> serde_json::Value {
+    use rusqlite::types::Value;
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Integer(i) => i.into(),
+        Value::Real(f) => f.into(),
+        Value::Text(s) => s.into(),
+        Value::Blob(b) => b.into(),
+    }
+}
+
+fn json_to_sql(value: &serde_json::Value) -> rusqlite::types::Value {
+    use rusqlite::types::Value;
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Integer(*b as i64),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Value::Text(s.clone()),
+        serde_json::Value::Array(bytes) => {
+            Value::Blob(bytes.iter().filter_map(|b| b.as_u64()).map(|b| b as u8).collect())
+        }
+        serde_json::Value::Object(_) => Value::Text(value.to_string()),
+    }
+}
+
+/// Check whether `from` (transitively) depends on `to`
+fn depends_on(conn: &Connection, from: &str, to: &str) -> Result<bool> {
+    let count: i32 = conn.query_row(
+        "WITH RECURSIVE chain(id) AS (
+            SELECT depend_id FROM task_dependencies WHERE task_id = ?1
+            UNION
+            SELECT d.depend_id FROM task_dependencies d JOIN chain c ON d.task_id = c.id
+        )
+        SELECT COUNT(*) FROM chain WHERE id = ?2",
+        [from, to],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+// ============================================================================
+// Core functions (return data, used by CLI and MCP)
+// ============================================================================
+
+/// Create a new task and return its ID
+pub fn create_task(conn: &Connection, task: &NewTask) -> Result<Created> {
+    let NewTask {
+        title,
+        description,
+        parent,
+        depends,
+        priority,
+        labels,
+        due,
+        duplicates,
+    } = *task;
+
+    if let Some(parent_id) = parent {
+        validate_id(parent_id)?;
+        if !task_exists(conn, parent_id)? {
+            bail!("Parent task '{}' not found.", parent_id);
+        }
+    }
+
+    for depend_id in depends {
+        validate_id(depend_id)?;
+        if !task_exists(conn, depend_id)? {
+            bail!("Dependency task '{}' not found.", depend_id);
+        }
+    }
+
+    let labels = labels
+        .iter()
+        .map(|l| normalize_label(l))
+        .collect::<Result<Vec<_>>>()?;
+    let due = due.map(|d| parse_due(conn, d)).transpose()?;
+
+    let similar = similar_tasks(conn, title, description, parent)?;
+    if !similar.is_empty() && refuse_duplicates(duplicates)? {
+        return Err(DuplicateError(similar).into());
+    }
+
+    let initial = workflow()?.first_of(TaskStatus::Pending);
+    let id = generate_id(conn, "tasks")?;
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("create {}", id))?;
+    journal.task(&tx, &id)?;
+    tx.execute(
+        "INSERT INTO tasks (id, title, description, parent_id, priority, status, done, due) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![id, title, description, parent, priority, initial.name, initial.kind.to_int(), due],
+    )?;
+    for depend_id in depends {
+        tx.execute(
+            "INSERT OR IGNORE INTO task_dependencies (task_id, depend_id) VALUES (?1, ?2)",
+            [&id, depend_id],
+        )?;
+    }
+    for label in &labels {
+        tx.execute(
+            "INSERT OR IGNORE INTO task_labels (task_id, label) VALUES (?1, ?2)",
+            [&id, label],
+        )?;
+    }
+    log_event(&tx, &id, "created", None, None, Some(title))?;
+    journal.commit(tx)?;
+    Ok(Created { id, similar })
+}
+
+/// List tasks with optional filters
+pub fn list_tasks(conn: &Connection, filter: &TaskFilter) -> Result<Vec<TaskSummary>> {
+    if let Some(pid) = filter.parent {
+        validate_id(pid)?;
+        if !task_exists(conn, pid)? {
+            bail!("Parent task '{}' not found.", pid);
+        }
+    }
+
+    let mut conditions: Vec<&str> = Vec::new();
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if let Some(state) = filter.status {
+        if workflow()?.state(state).is_none() {
+            bail!("Unknown status '{}'. See 'tsk workflow'.", state);
+        }
+        conditions.push("status = ?");
+        params.push(Box::new(state.to_string()));
+    } else if filter.inprogress && !filter.all {
+        conditions.push("done = ?");
+        params.push(Box::new(TaskStatus::InProgress.to_int()));
+    } else if filter.overdue || filter.due_before.is_some() || filter.assignee.is_some() {
+        // Due-date and assignee views cover all open tasks, not just pending ones
+        if !filter.all {
+            conditions.push("done < 2");
+        }
+    } else if !filter.all {
+        conditions.push("done = ?");
+        params.push(Box::new(TaskStatus::Pending.to_int()));
+    }
+
+    if let Some(p) = filter.parent {
+        conditions.push("parent_id = ?");
+        params.push(Box::new(p.to_string()));
+    }
+
+    if let Some(label) = filter.label {
+        conditions.push("id IN (SELECT task_id FROM task_labels WHERE label = ?)");
+        params.push(Box::new(normalize_label(label)?));
+    }
+
+    if filter.overdue {
+        conditions.push("due < date('now', 'localtime') AND done < 2");
+    }
+
+    if let Some(date) = filter.due_before {
+        conditions.push("due < ?");
+        params.push(Box::new(parse_due(conn, date)?));
+    }
+
+    if let Some(agent) = filter.assignee {
+        conditions.push("assignee = ?");
+        params.push(Box::new(agent.to_string()));
+    }
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let sql = format!(
+        "SELECT id, title, status, parent_id, (SELECT GROUP_CONCAT(depend_id) FROM task_dependencies WHERE task_id = tasks.id), priority,
+                (SELECT GROUP_CONCAT(label) FROM task_labels WHERE task_id = tasks.id),
+                due, COALESCE(due < date('now', 'localtime') AND done < 2, 0), assignee,
+                {}
+         FROM tasks {} ORDER BY created_at",
+        PROGRESS_COLUMNS, where_clause
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    let rows = stmt.query_map(params_refs.as_slice(), |row| {
+        Ok(TaskSummary {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            status: row.get(2)?,
+            parent_id: row.get(3)?,
+            depends: split_list(row.get(4)?),
+            priority: row.get(5)?,
+            labels: split_list(row.get(6)?),
+            due: row.get(7)?,
+            overdue: row.get(8)?,
+            assignee: row.get(9)?,
+            progress: Progress::from_counts(row.get(10)?, row.get(11)?),
+        })
+    })?;
+
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Done / total subtask counts of `tasks.id`, for `Progress`
+const PROGRESS_COLUMNS: &str = "(SELECT COUNT(*) FROM tasks c WHERE c.parent_id = tasks.id AND c.done = 2),
+                (SELECT COUNT(*) FROM tasks c WHERE c.parent_id = tasks.id AND c.done != 3)";
+
+/// Get full task details
+pub fn get_task(conn: &Connection, id: &str) -> Result<Task> {
+    validate_id(id)?;
+
+    let sql = format!(
+        "SELECT id, title, description, status, parent_id, created_at, priority, due,
+                COALESCE(due < date('now', 'localtime') AND done < 2, 0), assignee, started_at, lease_until,
+                {}
+         FROM tasks WHERE id = ?1",
+        PROGRESS_COLUMNS
+    );
+    let result = conn.query_row(
+        &sql,
+        [id],
+        |row| {
+            Ok(Task {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                description: row.get(2)?,
+                status: row.get(3)?,
+                parent_id: row.get(4)?,
+                created_at: Some(row.get(5)?),
+                priority: row.get(6)?,
+                due: row.get(7)?,
+                overdue: row.get(8)?,
+                assignee: row.get(9)?,
+                started_at: row.get(10)?,
+                lease_until: row.get(11)?,
+                progress: Progress::from_counts(row.get(12)?, row.get(13)?),
+                depends: Vec::new(),
+                labels: Vec::new(),
+                comments: Vec::new(),
+                memories: Vec::new(),
+            })
+        },
+    );
+
+    match result {
+        Ok(mut task) => {
+            task.depends = task_dependencies(conn, &task.id)?;
+            task.labels = task_labels(conn, &task.id)?;
+            task.comments = task_comments(conn, &task.id)?;
+            task.memories = linked_memories(conn, &task.id)?;
+            Ok(task)
+        }
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            bail!("Task '{}' not found.", id);
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Update task description and/or priority
+pub fn update_task(
+    conn: &Connection,
+    id: &str,
+    description: Option<&str>,
+    priority: Option<i32>,
+) -> Result<()> {
+    if description.is_none() && priority.is_none() {
+        validate_id(id)?;
+        bail!("Nothing to update: provide a description or priority.");
+    }
+
+    let changes = TaskEdit {
+        description,
+        priority,
+        ..Default::default()
+    };
+    edit_task(conn, id, &changes)
+}
+
+/// Check whether `ancestor` is `id` itself or one of its parents (transitively)
+fn is_ancestor_or_self(conn: &Connection, ancestor: &str, id: &str) -> Result<bool> {
+    let count: i32 = conn.query_row(
+        "WITH RECURSIVE up(id) AS (
+            SELECT ?1
+            UNION
+            SELECT t.parent_id FROM tasks t JOIN up ON t.id = up.id WHERE t.parent_id IS NOT NULL
+        )
+        SELECT COUNT(*) FROM up WHERE id = ?2",
+        [id, ancestor],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Edit task fields: title, description, priority, parent and dependencies
+pub fn edit_task(conn: &Connection, id: &str, changes: &TaskEdit) -> Result<()> {
+    validate_id(id)?;
+
+    if changes.is_empty() {
+        bail!("Nothing to edit: provide at least one field to change.");
+    }
+
+    if let Some(title) = changes.title
+        && title.trim().is_empty()
+    {
+        bail!("Title cannot be empty.");
+    }
+
+    // The old values compared and logged below are those the update replaces:
+    // IMMEDIATE takes the write lock before the first read
+    let tx = rusqlite::Transaction::new_unchecked(conn, rusqlite::TransactionBehavior::Immediate)?;
+    let task = get_task(&tx, id)?;
+
+    if let Some(Some(parent_id)) = changes.parent {
+        validate_id(parent_id)?;
+        if !task_exists(&tx, parent_id)? {
+            bail!("Parent task '{}' not found.", parent_id);
+        }
+        if is_ancestor_or_self(&tx, id, parent_id)? {
+            bail!("Cannot set parent: '{}' is '{}' or one of its subtasks (cycle).", parent_id, id);
+        }
+    }
+
+    if let Some(depends) = changes.depends {
+        for depend_id in depends {
+            validate_id(depend_id)?;
+            if !task_exists(&tx, depend_id)? {
+                bail!("Dependency task '{}' not found.", depend_id);
+            }
+            if depend_id == id {
+                bail!("Task '{}' cannot depend on itself.", id);
+            }
+            if depends_on(&tx, depend_id, id)? {
+                bail!("Cannot add: '{}' already depends on '{}' (cycle).", depend_id, id);
+            }
+        }
+    }
+
+    let due = match changes.due {
+        Some(Some(d)) => Some(Some(parse_due(&tx, d)?)),
+        Some(None) => Some(None),
+        None => None,
+    };
+
+    let journal = Journal::begin(&tx, &format!("edit {}", id))?;
+    journal.task(&tx, id)?;
+
+    if let Some(title) = changes.title
+        && title != task.title
+    {
+        tx.execute("UPDATE tasks SET title = ?1 WHERE id = ?2", [title, id])?;
+        log_event(&tx, id, "updated", Some("title"), Some(&task.title), Some(title))?;
+    }
+
+    if let Some(description) = changes.description
+        && description != task.description
+    {
+        tx.execute("UPDATE tasks SET description = ?1 WHERE id = ?2", [description, id])?;
+        log_event(&tx, id, "updated", Some("description"), Some(&task.description), Some(description))?;
+    }
+
+    if let Some(priority) = changes.priority
+        && priority != task.priority
+    {
+        tx.execute(
+            "UPDATE tasks SET priority = ?1 WHERE id = ?2",
+            rusqlite::params![priority, id],
+        )?;
+        log_event(
+            &tx,
+            id,
+            "updated",
+            Some("priority"),
+            Some(&task.priority.to_string()),
+            Some(&priority.to_string()),
+        )?;
+    }
+
+    if let Some(parent) = changes.parent
+        && parent != task.parent_id.as_deref()
+    {
+        tx.execute(
+            "UPDATE tasks SET parent_id = ?1 WHERE id = ?2",
+            rusqlite::params![parent, id],
+        )?;
+        log_event(&tx, id, "updated", Some("parent"), task.parent_id.as_deref(), parent)?;
+    }
+
+    if let Some(depends) = changes.depends {
+        let mut new_depends = depends.to_vec();
+        new_depends.sort();
+        new_depends.dedup();
+
+        if new_depends != task.depends {
+            tx.execute("DELETE FROM task_dependencies WHERE task_id = ?1", [id])?;
+            for depend_id in &new_depends {
+                tx.execute(
+                    "INSERT INTO task_dependencies (task_id, depend_id) VALUES (?1, ?2)",
+                    [id, depend_id],
+                )?;
+            }
+            let old = task.depends.join(",");
+            let new = new_depends.join(",");
+            log_event(
+                &tx,
+                id,
+                "updated",
+                Some("depend"),
+                Some(old.as_str()).filter(|s| !s.is_empty()),
+                Some(new.as_str()).filter(|s| !s.is_empty()),
+            )?;
+        }
+    }
+
+    if let Some(due) = due
+        && due != task.due
+    {
+        tx.execute("UPDATE tasks SET due = ?1 WHERE id = ?2", rusqlite::params![due, id])?;
+        log_event(&tx, id, "updated", Some("due"), task.due.as_deref(), due.as_deref())?;
+    }
+
+    journal.commit(tx)?;
+    Ok(())
+}
+
+/// Open tasks with a due date, grouped by day (earliest first, highest priority first within a day)
+pub fn agenda(conn: &Connection) -> Result<Vec<AgendaDay>> {
+    let today: String = conn.query_row("SELECT date('now', 'localtime')", [], |row| row.get(0))?;
+    let filter = TaskFilter {
+        due_before: Some("9999-12-31"),
+        ..Default::default()
+    };
+    let mut tasks = list_tasks(conn, &filter)?;
+    tasks.sort_by(|a, b| a.due.cmp(&b.due).then(b.priority.cmp(&a.priority)));
+
+    let mut days: Vec<AgendaDay> = Vec::new();
+    for task in tasks {
+        let date = task.due.clone().unwrap_or_default();
+        match days.last_mut() {
+            Some(day) if day.date == date => day.tasks.push(task),
+            _ => days.push(AgendaDay {
+                overdue: date < today,
+                today: date == today,
+                date,
+                tasks: vec![task],
+            }),
+        }
+    }
+    Ok(days)
+}
+
+/// Parent/child tree under `root` (or under every top-level task), via a
+/// recursive CTE over `parent_id`. `depth` limits levels below the roots.
+/// Unless `all`, done/cancelled tasks are pruned when no open task sits below them.
+pub fn task_tree(conn: &Connection, root: Option<&str>, depth: Option<usize>, all: bool) -> Result<Vec<TaskNode>> {
+    if let Some(root) = root {
+        validate_id(root)?;
+        if !task_exists(conn, root)? {
+            bail!("Task '{}' not found.", root);
+        }
+    }
+
+    // Orphans (parent deleted) count as top-level. Recursion stops on reaching
+    // the anchor again, so a parent cycle from legacy data can't loop forever.
+    let max_depth = depth.map_or(i64::MAX, |d| i64::try_from(d).unwrap_or(i64::MAX));
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE tree(id, parent_id, depth, anchor) AS (
+            SELECT id, NULL, 0, id FROM tasks
+            WHERE (?1 IS NULL AND (parent_id IS NULL OR parent_id NOT IN (SELECT id FROM tasks)))
+               OR id = ?1
+            UNION ALL
+            SELECT t.id, t.parent_id, tree.depth + 1, tree.anchor
+            FROM tasks t JOIN tree ON t.parent_id = tree.id
+            WHERE tree.depth < ?2 AND t.id != tree.anchor
+        )
+        SELECT tree.id, tree.parent_id FROM tree JOIN tasks USING (id)
+        ORDER BY tree.depth, tasks.created_at, tasks.rowid",
+    )?;
+    let mut query = |root: Option<&str>| -> Result<Vec<(String, Option<String>)>> {
+        Ok(stmt
+            .query_map(rusqlite::params![root, max_depth], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?)
+    };
+    let mut rows = query(root)?;
+
+    // Tasks in a parent cycle have no top-level ancestor; show each cycle from
+    // the task 'tsk doctor --fix' would detach
+    if root.is_none() {
+        let mut links = conn.prepare("SELECT id, parent_id FROM tasks")?;
+        let parents: BTreeMap<String, Option<String>> = links
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        for cycle in find_parent_cycles(&parents) {
+            rows.extend(query(cycle.last().map(|id| id.as_str()))?);
+        }
+    }
+
+    let mut summaries: HashMap<String, TaskSummary> = list_tasks(
+        conn,
+        &TaskFilter {
+            all: true,
+            ..Default::default()
+        },
+    )?
+    .into_iter()
+    .map(|t| (t.id.clone(), t))
+    .collect();
+
+    let mut roots = Vec::new();
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    for (id, parent) in rows {
+        match parent {
+            Some(pid) => children.entry(pid).or_default().push(id),
+            None => roots.push(id),
+        }
+    }
+
+    let workflow = workflow()?;
+    let mut seen = HashSet::new();
+    Ok(roots
+        .iter()
+        .filter_map(|id| build_node(id, &children, &mut summaries, &mut seen, workflow, all))
+        .collect())
+}
+
+fn build_node(
+    id: &str,
+    children: &HashMap<String, Vec<String>>,
+    summaries: &mut HashMap<String, TaskSummary>,
+    seen: &mut HashSet<String>,
+    workflow: &Workflow,
+    all: bool,
+) -> Option<TaskNode> {
+    if !seen.insert(id.to_string()) {
+        return None;
+    }
+    let task = summaries.remove(id)?;
+    let kids: Vec<TaskNode> = children
+        .get(id)
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|cid| build_node(cid, children, summaries, seen, workflow, all))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let closed = workflow.kind_of(&task.status).is_some_and(|k| k.is_closed());
+    if !all && closed && kids.is_empty() {
+        return None;
+    }
+    Some(TaskNode { task, children: kids })
+}
+
+/// Pick the next actionable task: highest-priority pending task whose
+/// dependencies are all done, oldest first on ties
+pub fn next_task(conn: &Connection) -> Result<Option<Task>> {
+    Ok(ready_tasks(conn, Some(1))?.pop())
+}
+
+/// Actionable tasks in `next_task` order: pending, with all dependencies done
+pub fn ready_tasks(conn: &Connection, limit: Option<usize>) -> Result<Vec<Task>> {
+    let initial = &workflow()?.first_of(TaskStatus::Pending).name;
+    let mut stmt = conn.prepare(
+        "SELECT id FROM tasks WHERE status = ?1 ORDER BY priority DESC, created_at, rowid",
+    )?;
+    let ids: Vec<String> = stmt
+        .query_map([initial], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut tasks = Vec::new();
+    for id in ids {
+        if limit.is_some_and(|n| tasks.len() >= n) {
+            break;
+        }
+        if unfinished_dependencies(conn, &id)?.is_empty() {
+            tasks.push(get_task(conn, &id)?);
+        }
+    }
+    Ok(tasks)
+}
+
+/// Start a task (pending -> in_progress), assigned to the current actor,
+/// optionally with a lease (see `claim_task`)
+pub fn start_task(conn: &Connection, id: &str, lease: Option<&str>) -> Result<()> {
+    claim_task(conn, Some(id), &current_actor(), lease)?;
+    Ok(())
+}
+
+/// Claim a task for `agent`: move it to in progress and set the assignee in a
+/// single conditional UPDATE, so two agents can never take the same task.
+/// Without an ID, claims the next actionable task (same order as `next_task`).
+/// With a lease, the task returns to pending unless renewed by `heartbeat_task`.
+/// Returns the claimed task ID, or `None` when nothing is ready.
+pub fn claim_task(conn: &Connection, id: Option<&str>, agent: &str, lease: Option<&str>) -> Result<Option<String>> {
+    let agent = agent.trim();
+    if agent.is_empty() {
+        bail!("Agent name cannot be empty.");
+    }
+    let lease_seconds = lease.map(parse_lease).transpose()?;
+
+    let workflow = workflow()?;
+    let target = workflow.first_of(TaskStatus::InProgress);
+
+    // IMMEDIATE takes the write lock up front, so the pick and the update can't interleave
+    let tx = rusqlite::Transaction::new_unchecked(conn, rusqlite::TransactionBehavior::Immediate)?;
+    release_expired_leases(&tx)?;
+
+    let (id, from) = match id {
+        Some(id) => {
+            validate_id(id)?;
+            let current = task_state(&tx, id)?;
+            match workflow.kind_of(&current) {
+                Some(TaskStatus::InProgress) => match task_assignee(&tx, id)? {
+                    Some(owner) => bail!("Task '{}' is already in progress (claimed by {}).", id, owner),
+                    None => bail!("Task '{}' is already in progress.", id),
+                },
+                Some(TaskStatus::Done) => bail!("Task '{}' is already done.", id),
+                Some(TaskStatus::Cancelled) => bail!("Task '{}' is cancelled.", id),
+                _ => {}
+            }
+            if !workflow.allows(&current, &target.name) {
+                bail!("Cannot start: workflow does not allow '{}' -> '{}'.", current, target.name);
+            }
+            (id.to_string(), current)
+        }
+        None => {
+            let initial = workflow.first_of(TaskStatus::Pending);
+            if !workflow.allows(&initial.name, &target.name) {
+                bail!("Cannot start: workflow does not allow '{}' -> '{}'.", initial.name, target.name);
+            }
+
+            let ready: Option<String> = tx
+                .query_row(
+                    "SELECT t.id FROM tasks t
+                     WHERE t.status = ?1 AND NOT EXISTS (
+                         SELECT 1 FROM task_dependencies d JOIN tasks dt ON dt.id = d.depend_id
+                         WHERE d.task_id = t.id AND dt.done != 2
+                     )
+                     ORDER BY t.priority DESC, t.created_at, t.rowid
+                     LIMIT 1",
+                    [&initial.name],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match ready {
+                Some(id) => (id, initial.name.clone()),
+                None => return Ok(None),
+            }
+        }
+    };
+
+    let journal = Journal::begin(&tx, &format!("start {}", id))?;
+    journal.task(&tx, &id)?;
+    let updated = tx.execute(
+        "UPDATE tasks SET status = ?1, done = ?2, assignee = ?3, started_at = CURRENT_TIMESTAMP,
+                lease_seconds = ?6, lease_until = datetime('now', '+' || ?6 || ' seconds')
+         WHERE id = ?4 AND status = ?5",
+        rusqlite::params![target.name, target.kind.to_int(), agent, id, from, lease_seconds],
+    )?;
+    if updated == 0 {
+        bail!("Task '{}' was claimed by someone else.", id);
+    }
+
+    log_event(&tx, &id, "started", Some("status"), Some(&from), Some(&target.name))?;
+    log_event(&tx, &id, "claimed", Some("assignee"), None, Some(agent))?;
+    journal.commit(tx)?;
+    Ok(Some(id))
+}
+
+/// Renew the lease on an in-progress task, by `lease` if given, else by its
+/// original length. Only the assignee (`agent`) may renew it.
+/// Returns the new expiry time (UTC).
+pub fn heartbeat_task(
+    conn: &Connection,
+    id: &str,
+    agent: &str,
+    lease: Option<&str>,
+) -> Result<String> {
+    validate_id(id)?;
+    let lease_seconds = lease.map(parse_lease).transpose()?;
+
+    let tx = conn.unchecked_transaction()?;
+    release_expired_leases(&tx)?;
+
+    let (done, current, assignee): (i32, Option<i64>, Option<String>) = tx
+        .query_row(
+            "SELECT done, lease_seconds, assignee FROM tasks WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?
+        .ok_or_else(|| anyhow::anyhow!("Task '{}' not found.", id))?;
+    if TaskStatus::from_int(done) != TaskStatus::InProgress {
+        bail!("Task '{}' is not in progress (its lease may have expired).", id);
+    }
+    match assignee {
+        None => bail!("Task '{}' is not assigned; claim it before renewing its lease.", id),
+        Some(owner) if owner != agent => {
+            bail!("Task '{}' is claimed by '{}'; only they can renew its lease.", id, owner)
+        }
+        Some(_) => {}
+    }
+    let Some(seconds) = lease_seconds.or(current) else {
+        bail!("Task '{}' has no lease; pass one with --lease (e.g. 30m).", id);
+    };
+
+    let until: String = tx.query_row(
+        "UPDATE tasks SET lease_seconds = ?1, lease_until = datetime('now', '+' || ?1 || ' seconds')
+         WHERE id = ?2 RETURNING lease_until",
+        rusqlite::params![seconds, id],
+        |row| row.get(0),
+    )?;
+    tx.commit()?;
+    Ok(until)
+}
+
+/// Complete a task, applying the project's `parent_completion` policy.
+/// Returns parents that were auto-completed as a result (nearest first).
+pub fn complete_task(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    validate_id(id)?;
+
+    let config = project_config()?;
+    let workflow = &config.workflow;
+    let current = task_state(conn, id)?;
+    let target = workflow.first_of(TaskStatus::Done);
+
+    match workflow.kind_of(&current) {
+        Some(TaskStatus::Done) => bail!("Task '{}' is already done.", id),
+        Some(TaskStatus::Cancelled) => bail!("Task '{}' is cancelled.", id),
+        _ => {}
+    }
+
+    ensure_dependencies_done(conn, id)?;
+
+    if !workflow.allows(&current, &target.name) {
+        bail!("Cannot complete: workflow does not allow '{}' -> '{}'.", current, target.name);
+    }
+
+    finish_task(conn, id, &current, target, &format!("done {}", id), "completed")
+}
+
+/// Move a task into a done-kind state, applying the project's
+/// `parent_completion` policy: refuse while subtasks are open (`block`) or
+/// complete parents whose last open subtask this was (`auto`).
+/// Returns parents that were auto-completed as a result (nearest first).
+fn finish_task(
+    conn: &Connection,
+    id: &str,
+    current: &str,
+    target: &WorkflowState,
+    operation: &str,
+    event: &str,
+) -> Result<Vec<String>> {
+    let config = project_config()?;
+
+    if config.parent_completion == ParentPolicy::Block {
+        let open = open_children(conn, id)?;
+        match open.len() {
+            0 => {}
+            1 => bail!("Cannot complete: subtask '{}' is still open.", open[0]),
+            _ => bail!("Cannot complete: subtasks '{}' are still open.", open.join("', '")),
+        }
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, operation)?;
+    set_task_state(&tx, &journal, id, current, target, event)?;
+    let completed_parents = auto_complete_parents(&tx, &journal, id)?;
+    journal.commit(tx)?;
+    Ok(completed_parents)
+}
+
+/// Under the `auto` policy, complete the parents of a task that was just
+/// closed (done or cancelled) once they have no open subtasks left, all the
+/// way up. Returns them nearest first.
+fn auto_complete_parents(conn: &Connection, journal: &Journal, id: &str) -> Result<Vec<String>> {
+    let config = project_config()?;
+    let mut completed_parents = Vec::new();
+    if config.parent_completion != ParentPolicy::Auto {
+        return Ok(completed_parents);
+    }
+
+    let done = config.workflow.first_of(TaskStatus::Done);
+    let mut child = id.to_string();
+    while let Some(parent) = get_task(conn, &child)?.parent_id
+        && can_auto_complete(conn, &parent)?
+    {
+        let current = task_state(conn, &parent)?;
+        set_task_state(conn, journal, &parent, &current, done, "completed")?;
+        completed_parents.push(parent.clone());
+        child = parent;
+    }
+    Ok(completed_parents)
+}
+
+/// Subtasks of a task that are neither done nor cancelled
+fn open_children(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT id FROM tasks WHERE parent_id = ?1 AND done < 2 ORDER BY created_at")?;
+    let ids = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(ids)
+}
+
+/// Whether an open parent can be completed automatically: no open subtasks,
+/// dependencies done and the workflow allows it
+fn can_auto_complete(conn: &Connection, id: &str) -> Result<bool> {
+    let workflow = workflow()?;
+    let Ok(current) = task_state(conn, id) else {
+        return Ok(false);
+    };
+    let open_kind = matches!(
+        workflow.kind_of(&current),
+        Some(TaskStatus::Pending | TaskStatus::InProgress)
+    );
+    Ok(open_kind
+        && open_children(conn, id)?.is_empty()
+        && unfinished_dependencies(conn, id)?.is_empty()
+        && workflow.allows(&current, &workflow.first_of(TaskStatus::Done).name))
+}
+
+/// Dependents of a task (tasks that list it as a dependency) with the given kind
+fn dependents_with_kind(conn: &Connection, id: &str, kind: TaskStatus) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id FROM task_dependencies d JOIN tasks t ON t.id = d.task_id
+         WHERE d.depend_id = ?1 AND t.done = ?2 ORDER BY t.created_at",
+    )?;
+    let ids = stmt
+        .query_map(rusqlite::params![id, kind.to_int()], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(ids)
+}
+
+/// Reopen a done or cancelled task (-> pending).
+/// Returns dependents that were already completed on top of it.
+pub fn reopen_task(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    validate_id(id)?;
+
+    let workflow = workflow()?;
+    let current = task_state(conn, id)?;
+    let target = workflow.first_of(TaskStatus::Pending);
+
+    if !workflow.kind_of(&current).is_some_and(|k| k.is_closed()) {
+        bail!("Task '{}' is not done or cancelled.", id);
+    }
+    if !workflow.allows(&current, &target.name) {
+        bail!("Cannot reopen: workflow does not allow '{}' -> '{}'.", current, target.name);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("reopen {}", id))?;
+    set_task_state(&tx, &journal, id, &current, target, "reopened")?;
+    journal.commit(tx)?;
+    dependents_with_kind(conn, id, TaskStatus::Done)
 }
 
-/// List tasks with optional filters
-pub fn list_tasks(
-    conn: &Connection,
-    inprogress: bool,
-    all: bool,
-    parent: Option<&str>,
-) -> Result<Vec<TaskSummary>> {
-    if let Some(pid) = parent {
-        validate_id(pid)?;
-        if !task_exists(conn, pid)? {
-            bail!("Parent task '{}' not found.", pid);
-        }
+/// Stop working on a task (in_progress -> pending)
+pub fn stop_task(conn: &Connection, id: &str) -> Result<()> {
+    validate_id(id)?;
+
+    let workflow = workflow()?;
+    let current = task_state(conn, id)?;
+    let target = workflow.first_of(TaskStatus::Pending);
+
+    if workflow.kind_of(&current) != Some(TaskStatus::InProgress) {
+        bail!("Task '{}' is not in progress.", id);
+    }
+    if !workflow.allows(&current, &target.name) {
+        bail!("Cannot stop: workflow does not allow '{}' -> '{}'.", current, target.name);
     }
 
-    let status_filter = if all {
-        None
-    } else if inprogress {
-        Some(1)
-    } else {
-        Some(0)
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("stop {}", id))?;
+    set_task_state(&tx, &journal, id, &current, target, "stopped")?;
+    journal.commit(tx)?;
+    Ok(())
+}
+
+/// Cancel a task (-> cancelled).
+/// Returns active dependents, which stay blocked by it, and parents that were
+/// auto-completed because this was their last open subtask (nearest first).
+pub fn cancel_task(conn: &Connection, id: &str) -> Result<(Vec<String>, Vec<String>)> {
+    validate_id(id)?;
+
+    let workflow = workflow()?;
+    let current = task_state(conn, id)?;
+    let Some(target) = workflow.find_kind(TaskStatus::Cancelled) else {
+        bail!("Workflow has no state of kind 'cancelled'.");
     };
 
-    let (sql, params): (String, Vec<Box<dyn rusqlite::ToSql>>) = match (status_filter, parent) {
-        (None, Some(p)) => (
-            "SELECT id, title, done, parent_id, depend_id FROM tasks WHERE parent_id = ?1 ORDER BY created_at".to_string(),
-            vec![Box::new(p.to_string()) as Box<dyn rusqlite::ToSql>],
-        ),
-        (Some(status), Some(p)) => (
-            "SELECT id, title, done, parent_id, depend_id FROM tasks WHERE done = ?1 AND parent_id = ?2 ORDER BY created_at".to_string(),
-            vec![Box::new(status) as Box<dyn rusqlite::ToSql>, Box::new(p.to_string())],
-        ),
-        (None, None) => (
-            "SELECT id, title, done, parent_id, depend_id FROM tasks ORDER BY created_at".to_string(),
-            vec![],
-        ),
-        (Some(status), None) => (
-            "SELECT id, title, done, parent_id, depend_id FROM tasks WHERE done = ?1 ORDER BY created_at".to_string(),
-            vec![Box::new(status) as Box<dyn rusqlite::ToSql>],
-        ),
+    match workflow.kind_of(&current) {
+        Some(TaskStatus::Done) => bail!("Task '{}' is already done.", id),
+        Some(TaskStatus::Cancelled) => bail!("Task '{}' is already cancelled.", id),
+        _ => {}
+    }
+    if !workflow.allows(&current, &target.name) {
+        bail!("Cannot cancel: workflow does not allow '{}' -> '{}'.", current, target.name);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("cancel {}", id))?;
+    set_task_state(&tx, &journal, id, &current, target, "cancelled")?;
+    let parents = auto_complete_parents(&tx, &journal, id)?;
+    journal.commit(tx)?;
+
+    let mut blocked = dependents_with_kind(conn, id, TaskStatus::Pending)?;
+    blocked.extend(dependents_with_kind(conn, id, TaskStatus::InProgress)?);
+    Ok((blocked, parents))
+}
+
+/// Move a task to any workflow state allowed by the transitions.
+/// Returns parents that were auto-completed when the target is a done or
+/// cancelled state.
+pub fn set_status(conn: &Connection, id: &str, state: &str) -> Result<Vec<String>> {
+    validate_id(id)?;
+
+    let workflow = workflow()?;
+    let Some(target) = workflow.state(state) else {
+        let names: Vec<&str> = workflow.states.iter().map(|s| s.name.as_str()).collect();
+        bail!("Unknown status '{}'. Valid: {}.", state, names.join(", "));
     };
+    let current = task_state(conn, id)?;
 
-    let mut stmt = conn.prepare(&sql)?;
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-    let rows = stmt.query_map(params_refs.as_slice(), |row| {
-        Ok((
-            row.get::<_, String>(0)?,
-            row.get::<_, String>(1)?,
-            row.get::<_, i32>(2)?,
-            row.get::<_, Option<String>>(3)?,
-            row.get::<_, Option<String>>(4)?,
-        ))
-    })?;
+    if current == target.name {
+        bail!("Task '{}' is already {}.", id, state);
+    }
+    if !workflow.allows(&current, &target.name) {
+        bail!("Cannot move: workflow does not allow '{}' -> '{}'.", current, target.name);
+    }
+    let operation = format!("status {} {}", id, target.name);
+    if target.kind == TaskStatus::Done {
+        ensure_dependencies_done(conn, id)?;
+        return finish_task(conn, id, &current, target, &operation, "moved");
+    }
 
-    let mut tasks = Vec::new();
-    for row in rows {
-        let (id, title, done, parent_id, depend_id) = row?;
-        tasks.push(TaskSummary {
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &operation)?;
+    set_task_state(&tx, &journal, id, &current, target, "moved")?;
+    let parents = match target.kind {
+        TaskStatus::Cancelled => auto_complete_parents(&tx, &journal, id)?,
+        _ => Vec::new(),
+    };
+    journal.commit(tx)?;
+    Ok(parents)
+}
+
+/// Ids of `id` and all of its descendants, parents before children
+fn subtree_ids(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    // UNION (not UNION ALL) stops parent cycles from legacy data recursing forever
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE sub(id, depth) AS (
+            SELECT ?1, 0
+            UNION
+            SELECT t.id, sub.depth + 1 FROM tasks t JOIN sub ON t.parent_id = sub.id
+        )
+        SELECT sub.id FROM sub JOIN tasks USING (id)
+        GROUP BY sub.id ORDER BY MIN(sub.depth), tasks.created_at, tasks.rowid",
+    )?;
+    let ids = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(ids)
+}
+
+/// Remove a task. Subtasks are handled according to `children`; with `dry_run`
+/// nothing is changed and the returned `Removal` is a preview.
+pub fn remove_task(
+    conn: &Connection,
+    id: &str,
+    children: ChildPolicy,
+    dry_run: bool,
+) -> Result<Removal> {
+    validate_id(id)?;
+
+    // The checks below and the removal see the same tasks: IMMEDIATE takes
+    // the write lock before the first read
+    let tx = rusqlite::Transaction::new_unchecked(conn, rusqlite::TransactionBehavior::Immediate)?;
+    if !task_exists(&tx, id)? {
+        bail!("Task '{}' not found.", id);
+    }
+
+    let subtree = subtree_ids(&tx, id)?;
+    let removed_ids: Vec<String> = match children {
+        ChildPolicy::Recursive => subtree.clone(),
+        _ => vec![id.to_string()],
+    };
+
+    let mut stmt = tx.prepare(
+        "SELECT id FROM tasks WHERE parent_id = ?1 ORDER BY created_at, rowid",
+    )?;
+    let child_ids: Vec<String> = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    drop(stmt);
+
+    let mut new_parent = None;
+    match children {
+        ChildPolicy::Refuse if !child_ids.is_empty() => {
+            bail!(
+                "Cannot remove: {} task(s) have '{}' as parent. \
+                 Use --recursive, --orphan-children or --reparent-to.",
+                child_ids.len(),
+                id
+            );
+        }
+        ChildPolicy::Reparent(to) => {
+            validate_id(to)?;
+            if !task_exists(&tx, to)? {
+                bail!("Parent task '{}' not found.", to);
+            }
+            if subtree.iter().any(|t| t == to) {
+                bail!("Cannot reparent to '{}': it is '{}' or one of its subtasks.", to, id);
+            }
+            new_parent = Some(to.to_string());
+        }
+        _ => {}
+    }
+
+    // Active tasks outside the removed set that would lose a dependency
+    let mut blocked: Vec<String> = Vec::new();
+    let mut stmt = tx.prepare(
+        "SELECT t.id FROM task_dependencies d JOIN tasks t ON t.id = d.task_id
+         WHERE d.depend_id = ?1 AND t.done < 2",
+    )?;
+    for rid in &removed_ids {
+        for dependent in stmt.query_map([rid], |row| row.get::<_, String>(0))? {
+            let dependent = dependent?;
+            if !removed_ids.contains(&dependent) && !blocked.contains(&dependent) {
+                blocked.push(dependent);
+            }
+        }
+    }
+    drop(stmt);
+
+    if !blocked.is_empty() && !dry_run {
+        if removed_ids.len() == 1 {
+            bail!(
+                "Cannot remove: {} active task(s) depend on '{}': {}.",
+                blocked.len(),
+                id,
+                blocked.join(", ")
+            );
+        }
+        bail!(
+            "Cannot remove: {} active task(s) depend on the subtree of '{}': {}.",
+            blocked.len(),
             id,
-            title,
-            status: TaskStatus::from_int(done),
-            parent_id,
-            depend_id,
-        });
+            blocked.join(", ")
+        );
     }
-    Ok(tasks)
+
+    let mut summaries: HashMap<String, TaskSummary> = list_tasks(
+        &tx,
+        &TaskFilter {
+            all: true,
+            ..Default::default()
+        },
+    )?
+    .into_iter()
+    .map(|t| (t.id.clone(), t))
+    .collect();
+    let removal = Removal {
+        removed: removed_ids.iter().filter_map(|rid| summaries.remove(rid)).collect(),
+        moved: match children {
+            ChildPolicy::Orphan | ChildPolicy::Reparent(_) => child_ids,
+            _ => Vec::new(),
+        },
+        new_parent,
+        blocked,
+    };
+
+    if dry_run {
+        return Ok(removal);
+    }
+
+    let journal = Journal::begin(&tx, &format!("remove {}", id))?;
+    for child in &removal.moved {
+        journal.task(&tx, child)?;
+        tx.execute(
+            "UPDATE tasks SET parent_id = ?1 WHERE id = ?2",
+            rusqlite::params![removal.new_parent, child],
+        )?;
+        let new_parent = removal.new_parent.as_deref();
+        log_event(&tx, child, "updated", Some("parent"), Some(id), new_parent)?;
+    }
+    for task in &removal.removed {
+        let rid = task.id.as_str();
+        journal.task(&tx, rid)?;
+        tx.execute(
+            "DELETE FROM task_dependencies WHERE task_id = ?1 OR depend_id = ?1",
+            [rid],
+        )?;
+        tx.execute("DELETE FROM task_comments WHERE task_id = ?1", [rid])?;
+        tx.execute("DELETE FROM task_labels WHERE task_id = ?1", [rid])?;
+        tx.execute("DELETE FROM task_memories WHERE task_id = ?1", [rid])?;
+        tx.execute("DELETE FROM tasks WHERE id = ?1", [rid])?;
+        log_event(&tx, rid, "removed", None, Some(&task.title), None)?;
+    }
+    journal.commit(tx)?;
+    Ok(removal)
 }
 
-/// Get full task details
-pub fn get_task(conn: &Connection, id: &str) -> Result<Task> {
+/// Add a dependency: `id` can't be done until `depend` is done
+pub fn add_dependency(conn: &Connection, id: &str, depend: &str) -> Result<()> {
     validate_id(id)?;
+    validate_id(depend)?;
 
-    let result = conn.query_row(
-        "SELECT id, title, description, done, parent_id, depend_id, created_at FROM tasks WHERE id = ?1",
-        [id],
-        |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-                row.get::<_, i32>(3)?,
-                row.get::<_, Option<String>>(4)?,
-                row.get::<_, Option<String>>(5)?,
-                row.get::<_, String>(6)?,
-            ))
-        },
-    );
+    if !task_exists(conn, id)? {
+        bail!("Task '{}' not found.", id);
+    }
+    if !task_exists(conn, depend)? {
+        bail!("Dependency task '{}' not found.", depend);
+    }
+    if id == depend {
+        bail!("Task '{}' cannot depend on itself.", id);
+    }
+    if depends_on(conn, depend, id)? {
+        bail!("Cannot add: '{}' already depends on '{}' (cycle).", depend, id);
+    }
 
-    match result {
-        Ok((id, title, description, done, parent_id, depend_id, created_at)) => Ok(Task {
-            id,
-            title,
-            description,
-            status: TaskStatus::from_int(done),
-            parent_id,
-            depend_id,
-            created_at: Some(created_at),
-        }),
-        Err(rusqlite::Error::QueryReturnedNoRows) => {
-            bail!("Task '{}' not found.", id);
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("depend add {} {}", id, depend))?;
+    journal.task(&tx, id)?;
+    let inserted = tx.execute(
+        "INSERT OR IGNORE INTO task_dependencies (task_id, depend_id) VALUES (?1, ?2)",
+        [id, depend],
+    )?;
+
+    if inserted == 0 {
+        bail!("Task '{}' already depends on '{}'.", id, depend);
+    }
+    log_event(&tx, id, "updated", Some("depend"), None, Some(depend))?;
+    journal.commit(tx)?;
+    Ok(())
+}
+
+/// Remove a dependency from a task
+pub fn remove_dependency(conn: &Connection, id: &str, depend: &str) -> Result<()> {
+    validate_id(id)?;
+    validate_id(depend)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("depend rm {} {}", id, depend))?;
+    journal.task(&tx, id)?;
+    let removed = tx.execute(
+        "DELETE FROM task_dependencies WHERE task_id = ?1 AND depend_id = ?2",
+        [id, depend],
+    )?;
+
+    if removed == 0 {
+        bail!("Task '{}' does not depend on '{}'.", id, depend);
+    }
+    log_event(&tx, id, "updated", Some("depend"), Some(depend), None)?;
+    journal.commit(tx)?;
+    Ok(())
+}
+
+/// Add a label to a task and return it as stored (normalised)
+pub fn add_label(conn: &Connection, id: &str, label: &str) -> Result<String> {
+    validate_id(id)?;
+    let label = normalize_label(label)?;
+
+    if !task_exists(conn, id)? {
+        bail!("Task '{}' not found.", id);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("label add {} {}", id, label))?;
+    journal.task(&tx, id)?;
+    let inserted = tx.execute(
+        "INSERT OR IGNORE INTO task_labels (task_id, label) VALUES (?1, ?2)",
+        [id, &label],
+    )?;
+
+    if inserted == 0 {
+        bail!("Task '{}' already has label '{}'.", id, label);
+    }
+    log_event(&tx, id, "updated", Some("label"), None, Some(&label))?;
+    journal.commit(tx)?;
+    Ok(label)
+}
+
+/// Remove a label from a task and return it as stored (normalised)
+pub fn remove_label(conn: &Connection, id: &str, label: &str) -> Result<String> {
+    validate_id(id)?;
+    let label = normalize_label(label)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("label rm {} {}", id, label))?;
+    journal.task(&tx, id)?;
+    let removed = tx.execute(
+        "DELETE FROM task_labels WHERE task_id = ?1 AND label = ?2",
+        [id, &label],
+    )?;
+
+    if removed == 0 {
+        bail!("Task '{}' has no label '{}'.", id, label);
+    }
+    log_event(&tx, id, "updated", Some("label"), Some(&label), None)?;
+    journal.commit(tx)?;
+    Ok(label)
+}
+
+/// Add a comment to a task and return the comment ID
+pub fn add_comment(conn: &Connection, id: &str, text: &str) -> Result<i64> {
+    validate_id(id)?;
+
+    if !task_exists(conn, id)? {
+        bail!("Task '{}' not found.", id);
+    }
+    if text.trim().is_empty() {
+        bail!("Comment text cannot be empty.");
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("comment {}", id))?;
+    journal.task(&tx, id)?;
+    tx.execute(
+        "INSERT INTO task_comments (task_id, text, author) VALUES (?1, ?2, ?3)",
+        rusqlite::params![id, text, current_actor()],
+    )?;
+    let comment_id = tx.last_insert_rowid();
+    log_event(&tx, id, "commented", None, None, Some(text))?;
+    journal.commit(tx)?;
+    Ok(comment_id)
+}
+
+/// Comments on a task, oldest first
+pub fn task_comments(conn: &Connection, id: &str) -> Result<Vec<TaskComment>> {
+    validate_id(id)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, task_id, text, author, created_at FROM task_comments
+         WHERE task_id = ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map([id], |row| {
+        Ok(TaskComment {
+            id: row.get(0)?,
+            task_id: row.get(1)?,
+            text: row.get(2)?,
+            author: row.get(3)?,
+            created_at: row.get(4)?,
+        })
+    })?;
+
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Get the event log for one task, or for all tasks when `id` is None.
+/// Returned oldest first; `last` keeps only the most recent N events.
+pub fn task_history(conn: &Connection, id: Option<&str>, last: Option<usize>) -> Result<Vec<TaskEvent>> {
+    if let Some(tid) = id {
+        validate_id(tid)?;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT id, task_id, event, field, old_value, new_value, actor, created_at
+         FROM task_events WHERE ?1 IS NULL OR task_id = ?1 ORDER BY id DESC",
+    )?;
+    let rows = stmt.query_map([id], |row| {
+        Ok(TaskEvent {
+            id: row.get(0)?,
+            task_id: row.get(1)?,
+            event: row.get(2)?,
+            field: row.get(3)?,
+            old_value: row.get(4)?,
+            new_value: row.get(5)?,
+            actor: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    })?;
+
+    let mut events: Vec<TaskEvent> = rows.collect::<rusqlite::Result<_>>()?;
+    if let Some(n) = last {
+        events.truncate(n);
+    }
+    events.reverse();
+
+    if let Some(tid) = id
+        && events.is_empty()
+        && !task_exists(conn, tid)?
+    {
+        bail!("Task '{}' not found.", tid);
+    }
+    Ok(events)
+}
+
+/// Reverse the last `count` journaled operations, newest first, by restoring
+/// the before-images of the rows they touched. Refuses if those rows no longer
+/// match the operation's after-images, i.e. something outside the journal
+/// (a lease expiry, a heartbeat) changed them since. With `dry_run` nothing
+/// changes. Returns the operations undone (or that would be).
+pub fn undo(conn: &Connection, count: usize, dry_run: bool) -> Result<Vec<UndoEntry>> {
+    if count == 0 {
+        bail!("Count must be at least 1.");
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let mut stmt = tx.prepare(
+        "SELECT id, operation, actor, created_at FROM undo_journal ORDER BY id DESC LIMIT ?1",
+    )?;
+    let entries = stmt
+        .query_map([count as i64], |row| {
+            Ok(UndoEntry {
+                id: row.get(0)?,
+                operation: row.get(1)?,
+                actor: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(stmt);
+
+    if entries.is_empty() {
+        bail!("Nothing to undo.");
+    }
+    if dry_run {
+        return Ok(entries);
+    }
+
+    for entry in &entries {
+        let mut stmt = tx.prepare(
+            "SELECT table_name, key_column, key, rows, after_rows FROM undo_rows
+             WHERE journal_id = ?1 ORDER BY rowid",
+        )?;
+        let images: Vec<(String, String, String, String, Option<String>)> = stmt
+            .query_map([entry.id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        for (table, column, key, _, after) in &images {
+            if !TASK_ROWS.iter().chain(MEMORY_ROWS).any(|&(t, c)| t == table && c == column) {
+                bail!("Undo journal refers to unknown rows {}.{}.", table, column);
+            }
+            if let Some(after) = after
+                && !same_rows(after, &rows_image(&tx, table, column, key)?)?
+            {
+                let what = if MEMORY_ROWS.contains(&(table.as_str(), column.as_str())) {
+                    "memory"
+                } else {
+                    "task"
+                };
+                bail!(
+                    "Can't undo '{}': {} '{}' was changed since by something undo doesn't track (lease expiry, heartbeat, ...).",
+                    entry.operation,
+                    what,
+                    key
+                );
+            }
         }
-        Err(e) => Err(e.into()),
+
+        // Clear the current rows first: an image can overlap another (a dependency
+        // between two removed tasks is saved with both of them)
+        for (table, column, key, _, _) in &images {
+            tx.execute(&format!("DELETE FROM {} WHERE {} = ?1", table, column), [key])?;
+        }
+        // Rowids already restored, so overlapping images restore a row once
+        let mut restored: HashSet<(&str, i64)> = HashSet::new();
+        for (table, _, _, rows, _) in &images {
+            // Images from before a migration may hold columns that have since been dropped
+            let mut stmt = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+            let existing: HashSet<String> = stmt
+                .query_map([], |row| row.get(1))?
+                .collect::<rusqlite::Result<_>>()?;
+
+            let rows: Vec<serde_json::Map<String, serde_json::Value>> = serde_json::from_str(rows)?;
+            for mut row in rows {
+                // Keep the original rowid (and so the order of equal timestamps)
+                // unless a newer row has taken it
+                if let Some(rowid) = row.get("rowid").and_then(serde_json::Value::as_i64) {
+                    if restored.contains(&(table.as_str(), rowid)) {
+                        continue;
+                    }
+                    let taken: bool = tx.query_row(
+                        &format!("SELECT EXISTS (SELECT 1 FROM {} WHERE rowid = ?1)", table),
+                        [rowid],
+                        |row| row.get(0),
+                    )?;
+                    if taken {
+                        row.remove("rowid");
+                    } else {
+                        restored.insert((table.as_str(), rowid));
+                    }
+                }
+                let (columns, values): (Vec<&String>, Vec<_>) = row
+                    .iter()
+                    .filter(|(column, _)| existing.contains(*column) || *column == "rowid")
+                    .map(|(column, value)| (column, json_to_sql(value)))
+                    .unzip();
+                let columns: Vec<String> = columns.iter().map(|c| format!("\"{}\"", c)).collect();
+                let sql = format!(
+                    "INSERT OR REPLACE INTO {} ({}) VALUES ({})",
+                    table,
+                    columns.join(", "),
+                    vec!["?"; columns.len()].join(", ")
+                );
+                tx.execute(&sql, rusqlite::params_from_iter(values))?;
+            }
+        }
+
+        for (table, _, key, _, _) in &images {
+            if table == "tasks" {
+                log_event(&tx, key, "undone", None, Some(&entry.operation), None)?;
+            }
+        }
+        tx.execute("DELETE FROM undo_rows WHERE journal_id = ?1", [entry.id])?;
+        tx.execute("DELETE FROM undo_journal WHERE id = ?1", [entry.id])?;
+    }
+    tx.commit()?;
+    Ok(entries)
+}
+
+/// Turn free text into an FTS5 query: every word must match (as a prefix)
+fn fts_query(input: &str) -> Result<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        bail!("Search query cannot be empty.");
+    }
+    Ok(terms.join(" "))
+}
+
+/// Divide scores by the best one, rounded to 3 places
+fn scale_scores(mut hits: Vec<SearchHit>) -> Vec<SearchHit> {
+    let best = hits.iter().map(|hit| hit.score).fold(0.0, f64::max);
+    if best > 0.0 {
+        for hit in &mut hits {
+            hit.score = (hit.score / best * 1000.0).round() / 1000.0;
+        }
+    }
+    hits
+}
+
+/// Ranked full-text search over tasks (title, description) and memories.
+/// `status` only matches tasks, so it leaves memories out. BM25 scores from
+/// the two indexes aren't comparable, so each kind is scaled to its best hit
+/// before the lists are merged.
+pub fn search(
+    conn: &Connection,
+    query: &str,
+    kind: Option<SearchKind>,
+    status: Option<&str>,
+    limit: usize,
+) -> Result<Vec<SearchHit>> {
+    let query = fts_query(query)?;
+    if let Some(state) = status
+        && workflow()?.state(state).is_none()
+    {
+        bail!("Unknown status '{}'. See 'tsk workflow'.", state);
+    }
+
+    let mut hits = Vec::new();
+
+    if kind != Some(SearchKind::Memory) {
+        // Title matches weigh more than description matches
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.title, t.status,
+                    snippet(tasks_fts, -1, '**', '**', '...', 12), -bm25(tasks_fts, 0.0, 4.0, 1.0)
+             FROM tasks_fts JOIN tasks t ON t.id = tasks_fts.id
+             WHERE tasks_fts MATCH ?1 AND (?2 IS NULL OR t.status = ?2)",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![query, status], |row| {
+            Ok(SearchHit {
+                kind: SearchKind::Task,
+                id: row.get(0)?,
+                title: Some(row.get(1)?),
+                status: Some(row.get(2)?),
+                snippet: row.get(3)?,
+                score: row.get(4)?,
+            })
+        })?;
+        hits.extend(scale_scores(rows.collect::<rusqlite::Result<_>>()?));
+    }
+
+    if kind != Some(SearchKind::Task) && status.is_none() {
+        let mut stmt = conn.prepare(
+            "SELECT m.id, snippet(memories_fts, 1, '**', '**', '...', 12), -bm25(memories_fts)
+             FROM memories_fts JOIN memories m ON m.id = memories_fts.id
+             WHERE memories_fts MATCH ?1",
+        )?;
+        let rows = stmt.query_map([&query], |row| {
+            Ok(SearchHit {
+                kind: SearchKind::Memory,
+                id: row.get(0)?,
+                title: None,
+                status: None,
+                snippet: row.get(1)?,
+                score: row.get(2)?,
+            })
+        })?;
+        hits.extend(scale_scores(rows.collect::<rusqlite::Result<_>>()?));
+    }
+
+    for hit in &mut hits {
+        hit.snippet = hit.snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// FTS5 query matching any word of `text` with 3 or more characters
+fn fts_any(text: &str) -> Option<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let word = word.to_lowercase();
+        if word.chars().count() >= 3 && !words.contains(&word) {
+            words.push(word);
+        }
+    }
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.iter().map(|w| format!("\"{}\"*", w)).collect::<Vec<_>>().join(" OR "))
+}
+
+/// Texts at least this similar count as duplicates on create
+const DUPLICATE_SIMILARITY: f64 = 0.6;
+
+/// Character trigrams of `text`, ignoring case, punctuation and spacing
+fn trigrams(text: &str) -> HashSet<String> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let chars: Vec<char> = format!(" {} ", words.join(" ")).chars().collect();
+    chars.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+/// Jaccard similarity of the trigram sets of `a` and `b`, from 0 to 1
+fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Similarity of two tasks: mostly the title, with the descriptions
+/// counting for 30% unless both are empty
+fn task_similarity(title: &str, description: &str, other_title: &str, other_description: &str) -> f64 {
+    let title_score = similarity(title, other_title);
+    if description.trim().is_empty() && other_description.trim().is_empty() {
+        return title_score;
     }
+    0.7 * title_score + 0.3 * similarity(description, other_description)
+}
+
+/// Keep scored candidates `(id, text, similarity)` that are close matches,
+/// most similar first
+fn close_matches(kind: SearchKind, candidates: Vec<(String, String, f64)>) -> Vec<Duplicate> {
+    let mut duplicates: Vec<Duplicate> = candidates
+        .into_iter()
+        .map(|(id, text, score)| Duplicate {
+            kind,
+            similarity: (score * 100.0).round() / 100.0,
+            id,
+            text,
+        })
+        .filter(|d| d.similarity >= DUPLICATE_SIMILARITY)
+        .collect();
+    duplicates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    duplicates
+}
+
+/// Open tasks under the same parent that closely match `title` and
+/// `description`
+pub fn similar_tasks(
+    conn: &Connection,
+    title: &str,
+    description: &str,
+    parent: Option<&str>,
+) -> Result<Vec<Duplicate>> {
+    let Some(query) = fts_any(title) else {
+        return Ok(Vec::new());
+    };
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.title, t.description FROM tasks_fts JOIN tasks t ON t.id = tasks_fts.id
+         WHERE tasks_fts MATCH ?1 AND t.done < 2 AND t.parent_id IS ?2
+         ORDER BY bm25(tasks_fts, 0.0, 4.0, 1.0) LIMIT 50",
+    )?;
+    let candidates = stmt
+        .query_map(rusqlite::params![query, parent], |row| {
+            let (id, other_title, other_description): (String, String, String) =
+                (row.get(0)?, row.get(1)?, row.get(2)?);
+            let score = task_similarity(title, description, &other_title, &other_description);
+            Ok((id, other_title, score))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(close_matches(SearchKind::Task, candidates))
 }
 
-/// Update task description
-pub fn update_task(conn: &Connection, id: &str, description: &str) -> Result<()> {
-    validate_id(id)?;
-
-    let updated = conn.execute(
-        "UPDATE tasks SET description = ?1 WHERE id = ?2",
-        [description, id],
+/// Memories whose content closely matches `content`
+pub fn similar_memories(conn: &Connection, content: &str) -> Result<Vec<Duplicate>> {
+    let Some(query) = fts_any(content) else {
+        return Ok(Vec::new());
+    };
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.content FROM memories_fts JOIN memories m ON m.id = memories_fts.id
+         WHERE memories_fts MATCH ?1 ORDER BY bm25(memories_fts) LIMIT 50",
     )?;
-
-    if updated == 0 {
-        bail!("Task '{}' not found.", id);
-    }
-    Ok(())
+    let candidates = stmt
+        .query_map([query], |row| {
+            let other: String = row.get(1)?;
+            let score = similarity(content, &other);
+            Ok((row.get(0)?, other, score))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(close_matches(SearchKind::Memory, candidates))
 }
 
-/// Start a task (pending -> in_progress)
-pub fn start_task(conn: &Connection, id: &str) -> Result<()> {
-    validate_id(id)?;
-
-    let status: i32 = conn
-        .query_row("SELECT done FROM tasks WHERE id = ?1", [id], |row| {
-            row.get(0)
-        })
-        .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => anyhow::anyhow!("Task '{}' not found.", id),
-            _ => e.into(),
-        })?;
+/// Rough token count used for context budgets: about 4 characters per token
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
 
-    if status != 0 {
-        if status == 1 {
-            bail!("Task '{}' is already in progress.", id);
-        } else {
-            bail!("Task '{}' is already done.", id);
-        }
+/// Shorten `text` to about `max` characters, cutting at a word boundary
+fn clip(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
     }
-
-    conn.execute("UPDATE tasks SET done = 1 WHERE id = ?1", [id])?;
-    Ok(())
+    let cut: String = text.chars().take(max).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(pos) if pos > max / 2 => &cut[..pos],
+        _ => &cut,
+    };
+    format!("{}...", cut.trim_end())
 }
 
-/// Complete a task
-pub fn complete_task(conn: &Connection, id: &str) -> Result<()> {
-    validate_id(id)?;
-
-    let status: i32 = conn
-        .query_row("SELECT done FROM tasks WHERE id = ?1", [id], |row| {
-            row.get(0)
-        })
-        .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => anyhow::anyhow!("Task '{}' not found.", id),
-            _ => e.into(),
-        })?;
-
-    if status == 2 {
-        bail!("Task '{}' is already done.", id);
+/// Memories worth showing next to `tasks`, most relevant first: pinned entries,
+/// entries linked to the tasks, entries matching words of their titles,
+/// gotchas and conventions, then the newest ones. At most `limit` unpinned
+/// entries; expired entries are left out.
+fn relevant_memories(conn: &Connection, tasks: &[Task], limit: usize) -> Result<Vec<Memory>> {
+    let today: String = conn.query_row("SELECT date('now', 'localtime')", [], |row| row.get(0))?;
+    let expired = |memory: &Memory| memory.expires_at.as_ref().is_some_and(|d| *d <= today);
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM memories m WHERE pinned = 1 ORDER BY created_at DESC, rowid DESC",
+        MEMORY_COLUMNS
+    ))?;
+    let pinned: Vec<Memory> = stmt.query_map([], memory_row)?.collect::<rusqlite::Result<_>>()?;
+    let mut memories = with_tags(conn, pinned)?;
+    memories.retain(|m| !expired(m));
+    let pinned = memories.len();
+
+    let mut candidates: Vec<Memory> = tasks.iter().flat_map(|t| t.memories.clone()).collect();
+
+    let titles: Vec<&str> = tasks.iter().map(|t| t.title.as_str()).collect();
+    if let Some(query) = fts_any(&titles.join(" ")) {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM memories_fts JOIN memories m ON m.id = memories_fts.id
+             WHERE memories_fts MATCH ?1 ORDER BY bm25(memories_fts) LIMIT ?2",
+            MEMORY_COLUMNS
+        ))?;
+        let matches: Vec<Memory> = stmt
+            .query_map(rusqlite::params![query, limit], memory_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        candidates.extend(with_tags(conn, matches)?);
     }
 
-    let depend_id: Option<String> = conn.query_row(
-        "SELECT depend_id FROM tasks WHERE id = ?1",
-        [id],
-        |row| row.get(0),
-    )?;
+    for kind in [MemoryKind::Gotcha, MemoryKind::Convention] {
+        let filter = MemoryFilter {
+            kind: Some(kind),
+            last: Some(limit),
+            ..Default::default()
+        };
+        candidates.extend(list_memories(conn, &filter)?);
+    }
+    let newest = MemoryFilter {
+        last: Some(limit + pinned),
+        ..Default::default()
+    };
+    candidates.extend(list_memories(conn, &newest)?);
 
-    if let Some(did) = depend_id {
-        match task_is_done(conn, &did)? {
-            Some(true) => {}
-            Some(false) => bail!("Cannot complete: depends on '{}' which is not done.", did),
-            None => {}
+    for memory in candidates {
+        if memories.len() - pinned >= limit {
+            break;
+        }
+        if !expired(&memory) && !memories.iter().any(|m| m.id == memory.id) {
+            memories.push(memory);
         }
     }
-
-    conn.execute("UPDATE tasks SET done = 2 WHERE id = ?1", [id])?;
-    Ok(())
+    Ok(memories)
 }
 
-/// Remove a task
-pub fn remove_task(conn: &Connection, id: &str) -> Result<()> {
-    validate_id(id)?;
-
-    if !task_exists(conn, id)? {
-        bail!("Task '{}' not found.", id);
+/// Compact markdown digest for the start of an agent session: in-progress
+/// tasks with descriptions, ready tasks, pinned and relevant memories, and
+/// recent activity. Entries are added in order of importance (in-progress
+/// tasks, pinned memories, ready tasks, other memories, activity) until about
+/// `budget` tokens are used; each section notes how many entries were left out.
+pub fn project_context(conn: &Connection, budget: usize) -> Result<String> {
+    let workflow = workflow()?;
+
+    let filter = TaskFilter {
+        inprogress: true,
+        ..Default::default()
+    };
+    let mut in_progress = Vec::new();
+    for summary in list_tasks(conn, &filter)? {
+        in_progress.push(get_task(conn, &summary.id)?);
     }
+    let ready = ready_tasks(conn, None)?;
+    let mut focus = in_progress.clone();
+    focus.extend(ready.iter().take(5).cloned());
+    let memories = relevant_memories(conn, &focus, 10)?;
+    let activity = task_history(conn, None, Some(10))?;
+
+    let task_line = |task: &Task| {
+        let mut details = Vec::new();
+        if let Some(agent) = &task.assignee {
+            details.push(format!("@{}", agent));
+        }
+        if task.priority != 0 {
+            details.push(format!("P{}", task.priority));
+        }
+        if let Some(due) = &task.due {
+            details.push(format!("due {}", due));
+        }
+        let details = if details.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", details.join(", "))
+        };
+        format!("- {} [{}] {}{}", task.id, workflow.marker(&task.status), task.title, details)
+    };
 
-    let dependents: i32 = conn.query_row(
-        "SELECT COUNT(*) FROM tasks WHERE depend_id = ?1 AND done < 2",
-        [id],
-        |row| row.get(0),
-    )?;
+    // (heading, hint for the rest, included entries, entries left out)
+    let mut sections: Vec<(&str, &str, Vec<String>, usize)> = vec![
+        ("In progress", "tsk list --inprogress", Vec::new(), 0),
+        ("Ready", "tsk list", Vec::new(), 0),
+        ("Memories", "tsk m list", Vec::new(), 0),
+        ("Recent activity", "tsk log --all", Vec::new(), 0),
+    ];
 
-    if dependents > 0 {
-        bail!(
-            "Cannot remove: {} active task(s) depend on '{}'.",
-            dependents,
-            id
-        );
+    let mut entries: Vec<(usize, String)> = Vec::new();
+    for task in &in_progress {
+        let mut entry = task_line(task);
+        let description = clip(task.description.trim(), 400);
+        for line in description.lines().filter(|l| !l.trim().is_empty()) {
+            entry.push_str(&format!("\n  {}", line.trim_end()));
+        }
+        entries.push((0, entry));
+    }
+    let memory_entry = |memory: &Memory| {
+        let content = memory.content.split_whitespace().collect::<Vec<_>>().join(" ");
+        format!(
+            "- [{}] {}{}{}",
+            memory.id,
+            memory_prefix(memory),
+            clip(&content, 300),
+            tags_suffix(&memory.tags)
+        )
+    };
+    let (pinned, other): (Vec<&Memory>, Vec<&Memory>) = memories.iter().partition(|m| m.pinned);
+    entries.extend(pinned.into_iter().map(|m| (2, memory_entry(m))));
+    entries.extend(ready.iter().map(|t| (1, task_line(t))));
+    entries.extend(other.into_iter().map(|m| (2, memory_entry(m))));
+    // Newest first, so the budget cuts the oldest events
+    for event in activity.iter().rev() {
+        let field = match (&event.field, event.event.as_str()) {
+            (Some(field), "updated") => format!(" {}", field),
+            _ => String::new(),
+        };
+        let when = event.created_at.get(..16).unwrap_or(&event.created_at);
+        let entry = format!("- {} {} {}{} ({})", when, event.task_id, event.event, field, event.actor);
+        entries.push((3, entry));
     }
 
-    let children: i32 = conn.query_row(
-        "SELECT COUNT(*) FROM tasks WHERE parent_id = ?1",
-        [id],
-        |row| row.get(0),
-    )?;
-
-    if children > 0 {
-        bail!("Cannot remove: {} task(s) have '{}' as parent.", children, id);
+    let title = "# Project context\n";
+    let mut used = estimate_tokens(title);
+    for (section, entry) in entries {
+        let (heading, _, included, omitted) = &mut sections[section];
+        let mut cost = estimate_tokens(&entry) + 1;
+        if included.is_empty() {
+            cost += estimate_tokens(heading) + 2;
+        }
+        // Keep each section in order: once an entry is cut, the rest is too
+        if *omitted > 0 || used + cost > budget {
+            *omitted += 1;
+            continue;
+        }
+        used += cost;
+        included.push(entry);
     }
 
-    conn.execute("DELETE FROM tasks WHERE id = ?1", [id])?;
-    Ok(())
+    let mut out = title.to_string();
+    for (heading, hint, included, omitted) in &sections {
+        if included.is_empty() && *omitted == 0 {
+            continue;
+        }
+        out.push_str(&format!("\n## {}\n", heading));
+        for entry in included {
+            out.push_str(entry);
+            out.push('\n');
+        }
+        if *omitted > 0 {
+            out.push_str(&format!("- ... {} more (`{}`)\n", omitted, hint));
+        }
+    }
+    if sections.iter().all(|(_, _, included, omitted)| included.is_empty() && *omitted == 0) {
+        out.push_str("\nNo tasks, memories or activity yet.\n");
+    }
+    Ok(out)
 }
 
 /// Get task IDs (for completions)
@@ -709,14 +4239,22 @@ pub fn init_project() -> Result<PathBuf> {
     Ok(db_path)
 }
 
+/// Open the database, migrate it and release expired leases
+fn connect(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    // Parallel agents share the database; wait for locks instead of failing
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    migrate_db(&conn)?;
+    let tx = conn.unchecked_transaction()?;
+    release_expired_leases(&tx)?;
+    tx.commit()?;
+    Ok(conn)
+}
+
 /// Find and open database, returns None if not initialized
 pub fn open_db() -> Result<Option<Connection>> {
     match find_db_path() {
-        Some(path) => {
-            let conn = Connection::open(&path)?;
-            migrate_db(&conn)?;
-            Ok(Some(conn))
-        }
+        Some(path) => Ok(Some(connect(&path)?)),
         None => Ok(None),
     }
 }
@@ -729,6 +4267,249 @@ pub fn require_db() -> Result<Connection> {
     }
 }
 
+// ============================================================================
+// Doctor (integrity checks)
+// ============================================================================
+
+/// Walk parent links and return every cycle found.
+/// Each cycle is ordered so that every task's parent is the next one,
+/// and the last task's parent is the first.
+fn find_parent_cycles(parents: &BTreeMap<String, Option<String>>) -> Vec<Vec<String>> {
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut cycles = Vec::new();
+
+    for start in parents.keys() {
+        let mut path: Vec<String> = Vec::new();
+        let mut current = Some(start.clone());
+
+        while let Some(id) = current {
+            if visited.contains(&id) {
+                break;
+            }
+            if let Some(pos) = path.iter().position(|p| *p == id) {
+                cycles.push(path[pos..].to_vec());
+                break;
+            }
+            path.push(id.clone());
+            current = parents.get(&id).cloned().flatten();
+        }
+
+        visited.extend(path);
+    }
+
+    cycles
+}
+
+/// Depth-first search over dependency edges, returning every cycle found.
+/// Ordered like `find_parent_cycles`: each task depends on the next one.
+fn find_dependency_cycles(edges: &BTreeMap<String, Vec<String>>) -> Vec<Vec<String>> {
+    fn visit(
+        node: &str,
+        edges: &BTreeMap<String, Vec<String>>,
+        finished: &mut HashMap<String, bool>,
+        stack: &mut Vec<String>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        finished.insert(node.to_string(), false);
+        stack.push(node.to_string());
+
+        for dep in edges.get(node).into_iter().flatten() {
+            match finished.get(dep) {
+                Some(false) => {
+                    let pos = stack.iter().position(|s| s == dep).unwrap_or(0);
+                    cycles.push(stack[pos..].to_vec());
+                }
+                Some(true) => {}
+                None => visit(dep, edges, finished, stack, cycles),
+            }
+        }
+
+        stack.pop();
+        finished.insert(node.to_string(), true);
+    }
+
+    let mut finished = HashMap::new();
+    let mut stack = Vec::new();
+    let mut cycles = Vec::new();
+    for node in edges.keys() {
+        if !finished.contains_key(node) {
+            visit(node, edges, &mut finished, &mut stack, &mut cycles);
+        }
+    }
+    cycles
+}
+
+fn format_cycle(cycle: &[String], arrow: &str) -> String {
+    let mut parts: Vec<&str> = cycle.iter().map(|s| s.as_str()).collect();
+    parts.push(&cycle[0]);
+    parts.join(arrow)
+}
+
+/// Check the database for integrity problems, optionally repairing them
+pub fn doctor(conn: &Connection, fix: bool) -> Result<Vec<Issue>> {
+    let tx = conn.unchecked_transaction()?;
+    let journal = fix.then(|| Journal::begin(&tx, "doctor --fix")).transpose()?;
+    let mut issues = Vec::new();
+
+    // Duplicate IDs. The primary keys keep IDs unique within each table, but
+    // older versions could give a task and a memory the same ID. Both stay
+    // reachable through their own commands, so this is reported, not fixed.
+    let mut stmt = tx.prepare("SELECT id FROM tasks WHERE id IN (SELECT id FROM memories) ORDER BY id")?;
+    let ids: Vec<String> = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    drop(stmt);
+
+    for id in ids {
+        let detail = format!("'{}' is both a task ID and a memory ID", id);
+        issues.push(Issue { kind: IssueKind::DuplicateId, id, detail, fixed: false });
+    }
+
+    // Invalid status: unknown workflow state, or kind column out of sync.
+    // Unknown states fall back to the first state of the stored kind
+    // (invalid kinds are treated as done by TaskStatus::from_int).
+    let workflow = workflow()?;
+    let mut stmt = tx.prepare("SELECT id, status, done FROM tasks")?;
+    let rows: Vec<(String, Option<String>, Option<i32>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    drop(stmt);
+
+    for (id, status, done) in rows {
+        let known = status.as_deref().and_then(|name| workflow.state(name));
+        let (target, detail) = match known {
+            Some(state) if Some(state.kind.to_int()) == done => continue,
+            Some(state) => (
+                state,
+                format!("kind value {:?} does not match state '{}'", done, state.name),
+            ),
+            None => {
+                let fallback = workflow
+                    .find_kind(TaskStatus::from_int(done.unwrap_or(2)))
+                    .unwrap_or_else(|| workflow.first_of(TaskStatus::Done));
+                (
+                    fallback,
+                    format!(
+                        "state {:?} is not in the workflow (treated as '{}')",
+                        status.unwrap_or_default(),
+                        fallback.name
+                    ),
+                )
+            }
+        };
+        if let Some(journal) = &journal {
+            journal.task(&tx, &id)?;
+            tx.execute(
+                "UPDATE tasks SET status = ?1, done = ?2 WHERE id = ?3",
+                rusqlite::params![target.name, target.kind.to_int(), id],
+            )?;
+        }
+        issues.push(Issue { kind: IssueKind::InvalidStatus, id, detail, fixed: fix });
+    }
+
+    // Orphaned parent references
+    let mut stmt = tx.prepare(
+        "SELECT id, parent_id FROM tasks WHERE parent_id IS NOT NULL AND parent_id NOT IN (SELECT id FROM tasks)",
+    )?;
+    let rows: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    drop(stmt);
+
+    for (id, parent_id) in rows {
+        if let Some(journal) = &journal {
+            journal.task(&tx, &id)?;
+            tx.execute("UPDATE tasks SET parent_id = NULL WHERE id = ?1", [&id])?;
+        }
+        issues.push(Issue {
+            kind: IssueKind::OrphanParent,
+            id,
+            detail: format!("parent '{}' not found", parent_id),
+            fixed: fix,
+        });
+    }
+
+    // Orphaned dependencies (either end missing)
+    let mut stmt = tx.prepare(
+        "SELECT task_id, depend_id FROM task_dependencies
+         WHERE task_id NOT IN (SELECT id FROM tasks) OR depend_id NOT IN (SELECT id FROM tasks)",
+    )?;
+    let rows: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    drop(stmt);
+
+    for (task_id, depend_id) in rows {
+        let missing = if task_exists(&tx, &task_id)? { &depend_id } else { &task_id };
+        let detail = format!("dependency {} @{}: '{}' not found", task_id, depend_id, missing);
+        if let Some(journal) = &journal {
+            journal.task(&tx, &task_id)?;
+            tx.execute(
+                "DELETE FROM task_dependencies WHERE task_id = ?1 AND depend_id = ?2",
+                [&task_id, &depend_id],
+            )?;
+        }
+        issues.push(Issue { kind: IssueKind::OrphanDependency, id: task_id, detail, fixed: fix });
+    }
+
+    // Parent cycles: break by detaching the task that closes the loop
+    let mut stmt = tx.prepare("SELECT id, parent_id FROM tasks")?;
+    let parents: BTreeMap<String, Option<String>> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    drop(stmt);
+
+    for cycle in find_parent_cycles(&parents) {
+        let last = cycle[cycle.len() - 1].clone();
+        if let Some(journal) = &journal {
+            journal.task(&tx, &last)?;
+            tx.execute("UPDATE tasks SET parent_id = NULL WHERE id = ?1", [&last])?;
+        }
+        issues.push(Issue {
+            kind: IssueKind::ParentCycle,
+            id: last,
+            detail: format!("parent cycle {}", format_cycle(&cycle, " ^")),
+            fixed: fix,
+        });
+    }
+
+    // Dependency cycles: break by dropping the edge that closes the loop
+    let mut stmt = tx.prepare("SELECT task_id, depend_id FROM task_dependencies ORDER BY task_id, depend_id")?;
+    let rows: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    drop(stmt);
+
+    let mut edges: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (task_id, depend_id) in rows {
+        edges.entry(task_id).or_default().push(depend_id);
+    }
+
+    for cycle in find_dependency_cycles(&edges) {
+        let last = cycle[cycle.len() - 1].clone();
+        if let Some(journal) = &journal {
+            journal.task(&tx, &last)?;
+            tx.execute(
+                "DELETE FROM task_dependencies WHERE task_id = ?1 AND depend_id = ?2",
+                [&last, &cycle[0]],
+            )?;
+        }
+        issues.push(Issue {
+            kind: IssueKind::DependencyCycle,
+            id: last,
+            detail: format!("dependency cycle {}", format_cycle(&cycle, " @")),
+            fixed: fix,
+        });
+    }
+
+    // Without repairs there is nothing to keep, not even an empty undo entry
+    match journal {
+        Some(journal) if issues.iter().any(|issue| issue.fixed) => journal.commit(tx)?,
+        _ => tx.rollback()?,
+    }
+    Ok(issues)
+}
+
 // ============================================================================
 // Memory core functions
 // ============================================================================
@@ -742,109 +4523,466 @@ fn memory_exists(conn: &Connection, id: &str) -> Result<bool> {
     Ok(count > 0)
 }
 
-/// Create a new memory entry
-pub fn create_memory(conn: &Connection, content: &str, tags: Option<&str>) -> Result<String> {
+/// Create a new memory entry, optionally linked to tasks
+pub fn create_memory(conn: &Connection, memory: &NewMemory) -> Result<Created> {
+    let NewMemory { content, kind, tags, review_after, expires_at, tasks, duplicates } = *memory;
+
+    let tags = tags.iter().map(|t| normalize_tag(t)).collect::<Result<Vec<_>>>()?;
+    for task_id in tasks {
+        validate_id(task_id)?;
+        if !task_exists(conn, task_id)? {
+            bail!("Task '{}' not found.", task_id);
+        }
+    }
+    let review_after = review_after.map(|d| parse_due(conn, d)).transpose()?;
+    let expires_at = expires_at.map(|d| parse_due(conn, d)).transpose()?;
+    let similar = similar_memories(conn, content)?;
+    if !similar.is_empty() && refuse_duplicates(duplicates)? {
+        return Err(DuplicateError(similar).into());
+    }
     let id = generate_id(conn, "memories")?;
 
-    conn.execute(
-        "INSERT INTO memories (id, content, tags) VALUES (?1, ?2, ?3)",
-        rusqlite::params![id, content, tags],
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("memory create {}", id))?;
+    journal.memory(&tx, &id)?;
+    tx.execute(
+        "INSERT INTO memories (id, content, kind, review_after, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![id, content, kind.map(MemoryKind::as_str), review_after, expires_at],
     )?;
+    for tag in &tags {
+        tx.execute(
+            "INSERT OR IGNORE INTO memory_tags (memory_id, tag) VALUES (?1, ?2)",
+            [&id, tag],
+        )?;
+    }
+    for task_id in tasks {
+        let linked = tx.execute(
+            "INSERT OR IGNORE INTO task_memories (task_id, memory_id) VALUES (?1, ?2)",
+            [task_id, &id],
+        )?;
+        if linked > 0 {
+            log_event(&tx, task_id, "updated", Some("memory"), None, Some(&id))?;
+        }
+    }
+    journal.commit(tx)?;
 
-    Ok(id)
+    Ok(Created { id, similar })
 }
 
-/// List memory entries
-pub fn list_memories(conn: &Connection, tag: Option<&str>, last: Option<usize>) -> Result<Vec<Memory>> {
-    let mut memories = Vec::new();
+/// Link a memory to a task
+pub fn link_memory(conn: &Connection, task_id: &str, memory_id: &str) -> Result<()> {
+    validate_id(task_id)?;
+    validate_id(memory_id)?;
 
-    if let Some(t) = tag {
-        let mut stmt = conn.prepare(
-            "SELECT id, content, tags, created_at FROM memories WHERE tags LIKE ?1 ORDER BY created_at DESC"
-        )?;
-        let rows = stmt.query_map([format!("%{}%", t)], |row| {
-            Ok(Memory {
-                id: row.get(0)?,
-                content: row.get(1)?,
-                tags: row.get(2)?,
-                created_at: row.get(3)?,
-            })
-        })?;
-        for mem in rows.flatten() {
-            memories.push(mem);
-        }
-    } else {
-        let mut stmt = conn.prepare(
-            "SELECT id, content, tags, created_at FROM memories ORDER BY created_at DESC"
-        )?;
-        let rows = stmt.query_map([], |row| {
-            Ok(Memory {
-                id: row.get(0)?,
-                content: row.get(1)?,
-                tags: row.get(2)?,
-                created_at: row.get(3)?,
-            })
-        })?;
-        for mem in rows.flatten() {
-            memories.push(mem);
-        }
+    if !task_exists(conn, task_id)? {
+        bail!("Task '{}' not found.", task_id);
+    }
+    if !memory_exists(conn, memory_id)? {
+        bail!("Memory '{}' not found.", memory_id);
     }
 
-    if let Some(n) = last {
-        memories.truncate(n);
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("link {} {}", task_id, memory_id))?;
+    journal.task(&tx, task_id)?;
+    let inserted = tx.execute(
+        "INSERT OR IGNORE INTO task_memories (task_id, memory_id) VALUES (?1, ?2)",
+        [task_id, memory_id],
+    )?;
+
+    if inserted == 0 {
+        bail!("Memory '{}' is already linked to '{}'.", memory_id, task_id);
+    }
+    log_event(&tx, task_id, "updated", Some("memory"), None, Some(memory_id))?;
+    journal.commit(tx)?;
+    Ok(())
+}
+
+/// Remove the link between a memory and a task
+pub fn unlink_memory(conn: &Connection, task_id: &str, memory_id: &str) -> Result<()> {
+    validate_id(task_id)?;
+    validate_id(memory_id)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("unlink {} {}", task_id, memory_id))?;
+    journal.task(&tx, task_id)?;
+    let removed = tx.execute(
+        "DELETE FROM task_memories WHERE task_id = ?1 AND memory_id = ?2",
+        [task_id, memory_id],
+    )?;
+
+    if removed == 0 {
+        bail!("Memory '{}' is not linked to '{}'.", memory_id, task_id);
     }
+    log_event(&tx, task_id, "updated", Some("memory"), Some(memory_id), None)?;
+    journal.commit(tx)?;
+    Ok(())
+}
+
+/// Memories linked to a task, oldest first
+fn linked_memories(conn: &Connection, task_id: &str) -> Result<Vec<Memory>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM task_memories l JOIN memories m ON m.id = l.memory_id
+         WHERE l.task_id = ?1 ORDER BY m.created_at, m.rowid",
+        MEMORY_COLUMNS
+    ))?;
+    let memories = stmt
+        .query_map([task_id], memory_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    with_tags(conn, memories)
+}
+
+/// IDs of the tasks a memory is linked to
+fn linked_tasks(conn: &Connection, memory_id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT l.task_id FROM task_memories l JOIN tasks t ON t.id = l.task_id
+         WHERE l.memory_id = ?1 ORDER BY t.created_at, t.rowid",
+    )?;
+    let ids = stmt.query_map([memory_id], |row| row.get::<_, String>(0))?;
+    Ok(ids.collect::<rusqlite::Result<_>>()?)
+}
+
+fn memory_tags_of(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT tag FROM memory_tags WHERE memory_id = ?1 ORDER BY tag")?;
+    let tags = stmt.query_map([id], |row| row.get::<_, String>(0))?;
+    Ok(tags.collect::<rusqlite::Result<_>>()?)
+}
 
+/// Columns of `memories m` read by `memory_row`; an entry is stale once its
+/// review date or expiry date has come
+const MEMORY_COLUMNS: &str = "m.id, m.content, m.created_at, m.updated_at, m.kind, m.pinned,
+                m.review_after, m.expires_at,
+                COALESCE(m.review_after <= date('now', 'localtime')
+                         OR m.expires_at <= date('now', 'localtime'), 0)";
+
+/// Build a `Memory` from a `MEMORY_COLUMNS` row; tags are filled in by `with_tags`
+fn memory_row(row: &rusqlite::Row) -> rusqlite::Result<Memory> {
+    let kind: Option<String> = row.get(4)?;
+    Ok(Memory {
+        id: row.get(0)?,
+        content: row.get(1)?,
+        kind: kind.and_then(|k| MemoryKind::parse(&k).ok()),
+        pinned: row.get(5)?,
+        review_after: row.get(6)?,
+        expires_at: row.get(7)?,
+        stale: row.get(8)?,
+        tags: Vec::new(),
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+        tasks: Vec::new(),
+    })
+}
+
+fn with_tags(conn: &Connection, mut memories: Vec<Memory>) -> Result<Vec<Memory>> {
+    for memory in &mut memories {
+        memory.tags = memory_tags_of(conn, &memory.id)?;
+    }
     Ok(memories)
 }
 
+/// List memory entries, newest first. With tags, only entries that have all
+/// of them (or, with `any`, at least one).
+pub fn list_memories(conn: &Connection, filter: &MemoryFilter) -> Result<Vec<Memory>> {
+    let tags = filter.tags.iter().map(|t| normalize_tag(t)).collect::<Result<Vec<_>>>()?;
+
+    let mut conditions = Vec::new();
+    let mut params: Vec<String> = Vec::new();
+    if !tags.is_empty() {
+        let placeholders = vec!["?"; tags.len()].join(", ");
+        let mut condition = format!(
+            "id IN (SELECT memory_id FROM memory_tags WHERE tag IN ({}) GROUP BY memory_id",
+            placeholders
+        );
+        if !filter.any {
+            condition.push_str(&format!(" HAVING COUNT(DISTINCT tag) = {}", tags.len()));
+        }
+        condition.push(')');
+        conditions.push(condition);
+        params.extend(tags);
+    }
+    if let Some(kind) = filter.kind {
+        conditions.push("kind = ?".to_string());
+        params.push(kind.as_str().to_string());
+    }
+    if filter.stale {
+        conditions.push(
+            "(review_after <= date('now', 'localtime') OR expires_at <= date('now', 'localtime'))"
+                .to_string(),
+        );
+    }
+
+    let mut sql = format!("SELECT {} FROM memories m", MEMORY_COLUMNS);
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY created_at DESC, rowid DESC");
+    if let Some(n) = filter.last {
+        sql.push_str(&format!(" LIMIT {}", n));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let memories = stmt
+        .query_map(rusqlite::params_from_iter(&params), memory_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    with_tags(conn, memories)
+}
+
+/// Memory tags with the number of entries using each, most used first
+pub fn memory_tags(conn: &Connection) -> Result<Vec<TagCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT tag, COUNT(*) FROM memory_tags WHERE memory_id IN (SELECT id FROM memories)
+         GROUP BY tag ORDER BY COUNT(*) DESC, tag",
+    )?;
+    let tags = stmt
+        .query_map([], |row| Ok(TagCount { tag: row.get(0)?, count: row.get(1)? }))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(tags)
+}
+
 /// Get a single memory entry
 pub fn get_memory(conn: &Connection, id: &str) -> Result<Memory> {
     validate_id(id)?;
 
-    let memory = conn.query_row(
-        "SELECT id, content, tags, created_at FROM memories WHERE id = ?1",
-        [id],
-        |row| {
-            Ok(Memory {
-                id: row.get(0)?,
-                content: row.get(1)?,
-                tags: row.get(2)?,
-                created_at: row.get(3)?,
-            })
-        },
-    ).context(format!("Memory '{}' not found.", id))?;
+    let mut memory = conn
+        .query_row(
+            &format!("SELECT {} FROM memories m WHERE id = ?1", MEMORY_COLUMNS),
+            [id],
+            memory_row,
+        )
+        .context(format!("Memory '{}' not found.", id))?;
+    memory.tags = memory_tags_of(conn, id)?;
+    memory.tasks = linked_tasks(conn, id)?;
 
     Ok(memory)
 }
 
-/// Search memories by content
+/// Search memories by content, best matches first
 pub fn search_memories(conn: &Connection, query: &str) -> Result<Vec<Memory>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, content, tags, created_at FROM memories WHERE content LIKE ?1 ORDER BY created_at DESC"
+    let query = fts_query(query)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM memories_fts JOIN memories m ON m.id = memories_fts.id
+         WHERE memories_fts MATCH ?1 ORDER BY bm25(memories_fts)",
+        MEMORY_COLUMNS
+    ))?;
+
+    let memories = stmt
+        .query_map([query], memory_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    with_tags(conn, memories)
+}
+
+/// Edit a memory entry in place, keeping its ID
+pub fn edit_memory(conn: &Connection, id: &str, changes: &MemoryEdit) -> Result<()> {
+    validate_id(id)?;
+
+    if changes.is_empty() {
+        bail!("Nothing to edit: provide content, kind, tags or dates to change.");
+    }
+
+    let memory = get_memory(conn, id)?;
+
+    if let Some(content) = changes.content
+        && content.trim().is_empty()
+    {
+        bail!("Content cannot be empty.");
+    }
+
+    let normalize =
+        |tags: &[String]| tags.iter().map(|t| normalize_tag(t)).collect::<Result<Vec<_>>>();
+    let mut tags = match changes.tags {
+        Some(tags) => normalize(tags)?,
+        None => memory.tags.clone(),
+    };
+    for tag in normalize(changes.add_tags)? {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    for tag in normalize(changes.remove_tags)? {
+        let Some(pos) = tags.iter().position(|t| *t == tag) else {
+            bail!("Memory '{}' has no tag '{}'.", id, tag);
+        };
+        tags.remove(pos);
+    }
+    tags.sort();
+    tags.dedup();
+
+    let date = |change: Option<Option<&str>>, current: &Option<String>| match change {
+        Some(Some(input)) => parse_due(conn, input).map(Some),
+        Some(None) => Ok(None),
+        None => Ok(current.clone()),
+    };
+    let review_after = date(changes.review_after, &memory.review_after)?;
+    let expires_at = date(changes.expires_at, &memory.expires_at)?;
+
+    let content = changes.content.unwrap_or(&memory.content);
+    let kind = changes.kind.unwrap_or(memory.kind);
+    if content == memory.content
+        && kind == memory.kind
+        && review_after == memory.review_after
+        && expires_at == memory.expires_at
+        && tags == memory.tags
+    {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("memory edit {}", id))?;
+    journal.memory(&tx, id)?;
+    tx.execute(
+        "UPDATE memories SET content = ?1, kind = ?2, review_after = ?3, expires_at = ?4,
+                updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?5",
+        rusqlite::params![content, kind.map(MemoryKind::as_str), review_after, expires_at, id],
     )?;
+    tx.execute("DELETE FROM memory_tags WHERE memory_id = ?1", [id])?;
+    for tag in &tags {
+        tx.execute(
+            "INSERT INTO memory_tags (memory_id, tag) VALUES (?1, ?2)",
+            [id, tag],
+        )?;
+    }
+    journal.commit(tx)?;
+    Ok(())
+}
 
-    let rows = stmt.query_map([format!("%{}%", query)], |row| {
-        Ok(Memory {
-            id: row.get(0)?,
-            content: row.get(1)?,
-            tags: row.get(2)?,
-            created_at: row.get(3)?,
-        })
-    })?;
+/// Pin or unpin a memory entry; pinned entries always appear in `tsk context`
+pub fn pin_memory(conn: &Connection, id: &str, pinned: bool) -> Result<()> {
+    let memory = get_memory(conn, id)?;
+    if memory.pinned == pinned {
+        bail!("Memory '{}' is already {}.", id, if pinned { "pinned" } else { "unpinned" });
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let op = if pinned { "memory pin" } else { "memory unpin" };
+    let journal = Journal::begin(&tx, &format!("{} {}", op, id))?;
+    journal.memory(&tx, id)?;
+    tx.execute("UPDATE memories SET pinned = ?1 WHERE id = ?2", rusqlite::params![pinned, id])?;
+    journal.commit(tx)?;
+    Ok(())
+}
+
+/// Remove a memory entry
+pub fn remove_memory(conn: &Connection, id: &str) -> Result<()> {
+    validate_id(id)?;
+
+    if !memory_exists(conn, id)? {
+        bail!("Memory '{}' not found.", id);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let journal = Journal::begin(&tx, &format!("memory rm {}", id))?;
+    journal.memory(&tx, id)?;
+    tx.execute("DELETE FROM memory_tags WHERE memory_id = ?1", [id])?;
+    tx.execute("DELETE FROM task_memories WHERE memory_id = ?1", [id])?;
+    tx.execute("DELETE FROM memories WHERE id = ?1", [id])?;
+    journal.commit(tx)?;
+    Ok(())
+}
+
+/// Marker on the first line of exported ADRs, so re-exports can find them
+const ADR_MARKER: &str = "<!-- tsk memory";
+
+/// Write decision memories to `dir` as numbered Architecture Decision Records
+/// (`0001-use-jwt-for-auth.md`), oldest first. The first line of a memory is
+/// the ADR title. A decision keeps the number it was first exported with
+/// (read back from the marker line); new decisions get numbers after the
+/// highest one in `dir`. Other files in `dir` are left alone, including ADRs
+/// of decisions that have since been removed.
+pub fn export_adr(conn: &Connection, dir: &Path) -> Result<Vec<PathBuf>> {
+    let filter = MemoryFilter {
+        kind: Some(MemoryKind::Decision),
+        ..Default::default()
+    };
+    let mut decisions = list_memories(conn, &filter)?;
+    decisions.reverse();
+
+    fs::create_dir_all(dir).context(format!("Cannot create '{}'.", dir.display()))?;
+
+    // Earlier exports by memory ID, and the highest number taken by any ADR
+    let mut exported: HashMap<String, (usize, PathBuf)> = HashMap::new();
+    let mut last = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(number) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.ends_with(".md"))
+            .and_then(|name| name.split('-').next())
+            .and_then(|prefix| prefix.parse::<usize>().ok())
+        else {
+            continue;
+        };
+        last = last.max(number);
+        let id = fs::read_to_string(&path).ok().and_then(|text| {
+            let line = text.lines().next()?.strip_prefix(ADR_MARKER)?;
+            Some(line.trim_end().strip_suffix("-->")?.trim().to_string())
+        });
+        if let Some(id) = id {
+            exported.insert(id, (number, path));
+        }
+    }
 
-    Ok(rows.filter_map(|r| r.ok()).collect())
-}
+    let mut paths = Vec::new();
+    for memory in &decisions {
+        let number = match exported.get(&memory.id) {
+            Some((number, _)) => *number,
+            None => {
+                last += 1;
+                last
+            }
+        };
+        let mut lines = memory.content.lines();
+        let title = lines.next().unwrap_or_default().trim_start_matches('#').trim();
+        let body = lines.collect::<Vec<_>>().join("\n");
+        let body = if body.trim().is_empty() { title } else { body.trim() };
+
+        let mut slug = String::new();
+        for c in title.to_lowercase().chars() {
+            if c.is_alphanumeric() {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+            if slug.chars().count() >= 50 {
+                break;
+            }
+        }
+        let slug = slug.trim_end_matches('-');
+        let slug = if slug.is_empty() { "decision" } else { slug };
 
-/// Remove a memory entry
-pub fn remove_memory(conn: &Connection, id: &str) -> Result<()> {
-    validate_id(id)?;
+        let mut text = format!("{} {} -->\n# {}. {}\n\n", ADR_MARKER, memory.id, number, title);
+        if let Some(created) = &memory.created_at {
+            text.push_str(&format!("Date: {}\n\n", created.get(..10).unwrap_or(created)));
+        }
+        text.push_str("## Status\n\nAccepted\n\n## Decision\n\n");
+        text.push_str(body);
+        text.push('\n');
+
+        let tasks = linked_tasks(conn, &memory.id)?;
+        if !memory.tags.is_empty() || !tasks.is_empty() {
+            text.push_str("\n## References\n\n");
+            if !memory.tags.is_empty() {
+                text.push_str(&format!("- Tags: {}\n", memory.tags.join(", ")));
+            }
+            if !tasks.is_empty() {
+                text.push_str(&format!("- Tasks: {}\n", tasks.join(", ")));
+            }
+        }
 
-    if !memory_exists(conn, id)? {
-        bail!("Memory '{}' not found.", id);
+        let path = dir.join(format!("{:04}-{}.md", number, slug));
+        // A changed title renames the file, keeping the number
+        if let Some((_, old)) = exported.get(&memory.id)
+            && *old != path
+        {
+            fs::remove_file(old)?;
+        }
+        fs::write(&path, text).context(format!("Cannot write '{}'.", path.display()))?;
+        paths.push(path);
     }
 
-    conn.execute("DELETE FROM memories WHERE id = ?1", [id])?;
-    Ok(())
+    Ok(paths)
 }
 
 const TSK_INSTRUCTIONS: &str = r#"## Task Management
@@ -852,25 +4990,53 @@ const TSK_INSTRUCTIONS: &str = r#"## Task Management
 This project uses `tsk` for task tracking.
 
 ### Task Commands
-- `tsk create "<title>" "<description>"` — create task, returns ID
+- `tsk context [--budget 2000]` — start here: in-progress and ready tasks, key memories, recent activity
+- `tsk create "<title>" "<description>"` — create task, returns ID; warns about similar open tasks under the same parent (update one of those instead; `--unique` refuses)
 - `tsk create "<title>" "<desc>" --parent <id>` — create subtask
-- `tsk create "<title>" "<desc>" --depend <id>` — task with dependency
+- `tsk create "<title>" "<desc>" --depend <id>` — task with dependency (repeatable)
+- `tsk depend add <id> <dep>` / `tsk depend rm <id> <dep>` — manage dependencies
+- `tsk label add <id> <label>` / `tsk label rm <id> <label>` — categorise tasks; `tsk list --label <label>` filters
 - `tsk list` — show pending tasks
 - `tsk list --inprogress` — show in progress tasks
 - `tsk list --all` — show all tasks
 - `tsk list --parent <id>` — show subtasks only
 - `tsk show <id>` — task details
-- `tsk start <id>` — mark as in progress
+- `tsk search "<words>" [--kind task|memory] [--status <state>]` — ranked search over tasks and memories
+- `tsk edit <id> [--title] [--description] [--parent|--no-parent] [--depend|--no-depend] [--due|--no-due]` — edit task
+- `tsk next` — show the next actionable task (highest priority, dependencies done)
+- `tsk claim [<id>] --as <agent>` — atomically start and assign a task (next ready one if no ID); `tsk list --mine` shows yours
+- `tsk start <id> --lease 30m` / `tsk claim --lease 30m` — task returns to pending unless you run `tsk heartbeat <id> [--as <agent>]` before the lease expires
+- `tsk tree [<id>] [--depth N]` — parent/child hierarchy with status and dependencies
+- `tsk agenda` / `tsk list --overdue` — deadlines (set with `--due 2026-11-01`, `--due +3d`)
+- `tsk start <id>` — mark as in progress and assign to yourself
 - `tsk done <id>` — mark complete
-- `tsk remove <id>` — delete task
+- `tsk stop <id>` — back to pending from in progress
+- `tsk reopen <id>` — reopen a done/cancelled task
+- `tsk cancel <id>` — cancel a task
+- `tsk status <id> <state>` — move to any workflow state (see `tsk workflow`)
+- `tsk remove <id>` — delete task; with subtasks add `--recursive --yes`, `--orphan-children` or `--reparent-to <id>` (`--dry-run` previews)
+- `tsk comment <id> "<text>"` — add a progress note (keeps history, unlike update)
+- `tsk comments <id>` — list notes on a task
+- `tsk log <id>` / `tsk log --all` — task history (who changed what, when)
+- `tsk undo [N] [--dry-run]` — reverse the last N changes (remove, edit, done, memory rm, ...)
+- `tsk doctor [--fix]` — check (and repair) broken links and cycles
+- Add `--json` to any command for JSON output (`--jsonl` for one list item per line); errors come back as `{"error": ...}` with a non-zero exit
 
 ### Memory Commands (project knowledge)
 - `tsk m "<text>"` — store important info
-- `tsk m "<text>" --tags api,auth` — store with tags
+- `tsk m "<text>" --tags api,auth` — store with tags; warns about similar entries (`tsk m edit` one of those instead; `--unique` refuses)
+- `tsk m "<text>" --task <id>` / `tsk link <task> <memory>` — link knowledge to a task (`tsk show` lists it)
 - `tsk m list` — show all memories
-- `tsk m list --tag api` — filter by tag
+- `tsk m list --tag api --tag auth [--any]` — filter by tags (all, or any with `--any`)
+- `tsk m "<text>" --kind decision|convention|gotcha` — typed entry; `tsk m list --kind gotcha` filters
+- `tsk m export --adr docs/adr` — write decisions as numbered ADR files
+- `tsk m pin <id>` / `tsk m unpin <id>` — pinned memories always appear in `tsk context`
+- `tsk m "<text>" --review-after +30d` / `--expires 2026-12-31` — facts that may go out of date
+- `tsk m list --stale` — entries marked stale may be outdated: verify them and update (`tsk m edit`) or remove them before acting on them
+- `tsk m tags` — tags with entry counts
 - `tsk m search "<query>"` — search memories
 - `tsk m show <id>` — show full memory
+- `tsk m edit <id> [--content "<text>"] [--kind <kind>] [--tags a,b] [--add-tag t] [--rm-tag t]` — fix a memory in place (keeps the ID)
 - `tsk m rm <id>` — remove memory
 
 ### When to use
@@ -881,46 +5047,65 @@ This project uses `tsk` for task tracking.
 `abc123  [ ]  Pending task ^parent @dependency`
 `abc123  [>]  In progress task`
 `abc123  [x]  Done task`
+`abc123  [-]  Cancelled task`
 "#;
 
-fn install_agent_rules(current_dir: &PathBuf, agents: &[usize]) -> Result<()> {
-    let agent_configs: Vec<(&str, PathBuf)> = vec![
-        ("Claude Code", current_dir.join("CLAUDE.md")),
-        ("GitHub Copilot", current_dir.join(".github").join("copilot-instructions.md")),
-        ("Cursor", current_dir.join(".cursorrules")),
-        ("Windsurf", current_dir.join(".windsurfrules")),
+/// Write tsk rules into each agent's instructions file; returns what happened to each file
+fn install_agent_rules(current_dir: &Path, agents: &[usize]) -> Result<Vec<RulesFile>> {
+    let agent_configs: Vec<PathBuf> = vec![
+        current_dir.join("CLAUDE.md"),
+        current_dir.join(".github").join("copilot-instructions.md"),
+        current_dir.join(".cursorrules"),
+        current_dir.join(".windsurfrules"),
     ];
 
+    let mut files = Vec::new();
     for &idx in agents {
-        let (name, path) = &agent_configs[idx];
+        let path = &agent_configs[idx];
 
         // Create parent directory if needed
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent)?;
-            }
+        if let Some(parent) = path.parent()
+            && !parent.exists()
+        {
+            fs::create_dir_all(parent)?;
         }
 
-        if path.exists() {
+        let action = if path.exists() {
             // Append to existing file
-            let existing = fs::read_to_string(&path)?;
+            let existing = fs::read_to_string(path)?;
             if !existing.contains("## Task Management") {
                 let new_content = format!("{}\n\n{}", existing.trim_end(), TSK_INSTRUCTIONS);
-                fs::write(&path, new_content)?;
-                println!("  Updated: {}", path.display());
+                fs::write(path, new_content)?;
+                "updated"
             } else {
-                println!("  Skipped: {} (already has tsk rules)", path.display());
+                "skipped"
             }
         } else {
             // Create new file
-            fs::write(&path, TSK_INSTRUCTIONS)?;
-            println!("  Created: {}", path.display());
-        }
-
-        let _ = name; // suppress unused warning
+            fs::write(path, TSK_INSTRUCTIONS)?;
+            "created"
+        };
+        files.push(RulesFile { path: path.clone(), action });
     }
 
-    Ok(())
+    Ok(files)
+}
+
+/// An agent instructions file touched by `tsk init --rules`
+#[derive(Serialize)]
+struct RulesFile {
+    path: PathBuf,
+    action: &'static str,
+}
+
+fn print_rules_files(files: &[RulesFile]) {
+    for file in files {
+        match file.action {
+            "updated" => println!("  Updated: {}", file.path.display()),
+            "skipped" => println!("  Skipped: {} (already has tsk rules)", file.path.display()),
+            _ => println!("  Created: {}", file.path.display()),
+        }
+    }
 }
 
 fn parse_rules_arg(rules: &str) -> Vec<usize> {
@@ -938,10 +5123,62 @@ fn parse_rules_arg(rules: &str) -> Vec<usize> {
     indices
 }
 
+/// How command results are printed, set once from the global --json/--jsonl flags
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Text,
+    Json,
+    Jsonl,
+}
+
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+/// True when results should be printed as JSON instead of text
+fn json_output() -> bool {
+    OUTPUT.get().is_some_and(|o| *o != Output::Text)
+}
+
+/// Print a command result as JSON: pretty by default, one compact line per
+/// list item with --jsonl
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    if OUTPUT.get() != Some(&Output::Jsonl) {
+        println!("{}", serde_json::to_string_pretty(value)?);
+        return Ok(());
+    }
+    match serde_json::to_value(value)? {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                println!("{}", item);
+            }
+        }
+        other => println!("{}", other),
+    }
+    Ok(())
+}
+
+/// JSON form of a failed command; duplicate refusals list the close matches
+fn error_json(e: &anyhow::Error) -> serde_json::Value {
+    match e.downcast_ref::<DuplicateError>() {
+        Some(DuplicateError(duplicates)) => serde_json::json!({
+            "error": "Similar entries already exist. Update one of them, or pass --force to create anyway.",
+            "duplicates": duplicates,
+        }),
+        None => serde_json::json!({ "error": format!("{:#}", e) }),
+    }
+}
+
 fn cmd_init(rules: Option<&str>) -> Result<()> {
     let current_dir = env::current_dir().context("Failed to get current directory")?;
     let tsk_dir = current_dir.join(".tsk");
 
+    if json_output() {
+        // Never prompt: only install the rules asked for with --rules
+        let path = init_project()?;
+        let selected = rules.map(parse_rules_arg).unwrap_or_default();
+        let files = install_agent_rules(&current_dir, &selected)?;
+        return print_json(&serde_json::json!({ "success": true, "path": path, "rules": files }));
+    }
+
     let already_initialized = tsk_dir.exists();
 
     if !already_initialized {
@@ -963,7 +5200,7 @@ fn cmd_init(rules: Option<&str>) -> Result<()> {
                 println!("Installing agent rules...");
             }
             println!();
-            install_agent_rules(&current_dir, &selected)?;
+            print_rules_files(&install_agent_rules(&current_dir, &selected)?);
             println!();
             println!("Agent rules installed.");
         }
@@ -977,109 +5214,715 @@ fn cmd_init(rules: Option<&str>) -> Result<()> {
         ];
 
         println!();
-        println!("Install AI agent rules? (Space to select, Enter to confirm)");
+        println!("Install AI agent rules? (Space to select, Enter to confirm)");
+
+        let selections = MultiSelect::new()
+            .items(&agents)
+            .interact_opt()?;
+
+        if let Some(selected) = selections
+            && !selected.is_empty()
+        {
+            println!();
+            print_rules_files(&install_agent_rules(&current_dir, &selected)?);
+            println!();
+            println!("Agent rules installed.");
+        }
+    } else {
+        println!("Already initialized. Use --rules to add agent rules.");
+    }
+
+    Ok(())
+}
+
+fn cmd_create(conn: &Connection, task: &NewTask) -> Result<()> {
+    let created = create_task(conn, task)?;
+    if json_output() {
+        return print_json(&created);
+    }
+    println!("{}", created.id);
+    warn_similar(&created.similar);
+    Ok(())
+}
+
+/// Point out close matches that existed before a create
+fn warn_similar(similar: &[Duplicate]) {
+    if similar.is_empty() {
+        return;
+    }
+    eprintln!("Warning: created despite similar existing entries:");
+    for duplicate in similar {
+        eprintln!(
+            "  {}  {} ({:.0}% similar)",
+            duplicate.id,
+            truncate_content(&duplicate.text, 50),
+            duplicate.similarity * 100.0
+        );
+    }
+}
+
+fn cmd_list(conn: &Connection, filter: &TaskFilter) -> Result<()> {
+    let workflow = workflow()?;
+    let tasks = list_tasks(conn, filter)?;
+    if json_output() {
+        return print_json(&tasks);
+    }
+
+    for task in tasks {
+        println!("{}{}", task_line(workflow, &task, true), due_suffix(&task));
+    }
+
+    Ok(())
+}
+
+fn due_suffix(task: &TaskSummary) -> String {
+    match (&task.due, task.overdue) {
+        (Some(due), true) => format!(" ~{} (overdue)", due),
+        (Some(due), false) => format!(" ~{}", due),
+        (None, _) => String::new(),
+    }
+}
+
+fn cmd_tree(conn: &Connection, root: Option<&str>, depth: Option<usize>, all: bool) -> Result<()> {
+    let workflow = workflow()?;
+    let nodes = task_tree(conn, root, depth, all)?;
+    if json_output() {
+        return print_json(&nodes);
+    }
+
+    if nodes.is_empty() {
+        println!("No tasks.");
+        return Ok(());
+    }
+    for node in &nodes {
+        println!("{}{}", task_line(workflow, &node.task, root.is_some()), due_suffix(&node.task));
+        print_subtree(workflow, &node.children, "");
+    }
+    Ok(())
+}
+
+fn print_subtree(workflow: &Workflow, nodes: &[TaskNode], prefix: &str) {
+    for (i, node) in nodes.iter().enumerate() {
+        let last = i + 1 == nodes.len();
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        println!(
+            "{}{}{}{}",
+            prefix,
+            branch,
+            task_line(workflow, &node.task, false),
+            due_suffix(&node.task)
+        );
+        print_subtree(workflow, &node.children, &format!("{}{}", prefix, indent));
+    }
+}
+
+fn cmd_agenda(conn: &Connection) -> Result<()> {
+    let workflow = workflow()?;
+    let days = agenda(conn)?;
+    if json_output() {
+        return print_json(&days);
+    }
+
+    if days.is_empty() {
+        println!("No open tasks with a due date.");
+        return Ok(());
+    }
+
+    for (i, day) in days.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let note = if day.overdue {
+            "  (overdue)"
+        } else if day.today {
+            "  (today)"
+        } else {
+            ""
+        };
+        println!("{}{}", day.date, note);
+        for task in &day.tasks {
+            println!("  {}", task_line(workflow, task, true));
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_context(conn: &Connection, budget: usize) -> Result<()> {
+    let digest = project_context(conn, budget)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "context": digest }));
+    }
+    print!("{}", digest);
+    Ok(())
+}
+
+fn cmd_search(
+    conn: &Connection,
+    query: &str,
+    kind: Option<SearchKind>,
+    status: Option<&str>,
+    limit: usize,
+) -> Result<()> {
+    let hits = search(conn, query, kind, status, limit)?;
+    if json_output() {
+        return print_json(&hits);
+    }
+
+    if hits.is_empty() {
+        println!("No matches found.");
+        return Ok(());
+    }
+
+    let workflow = workflow()?;
+    for hit in hits {
+        match (hit.kind, &hit.title, &hit.status) {
+            (SearchKind::Task, Some(title), Some(status)) => {
+                println!("{}  [{}]  {}", hit.id, workflow.marker(status), title);
+            }
+            _ => println!("[{}] memory", hit.id),
+        }
+        println!("    {}", hit.snippet);
+    }
+
+    Ok(())
+}
+
+/// One list line without the due marker: id, state marker, title and suffixes
+fn task_line(workflow: &Workflow, task: &TaskSummary, with_parent: bool) -> String {
+    let mut suffix = String::new();
+    if let Some(pid) = &task.parent_id
+        && with_parent
+    {
+        suffix.push_str(&format!(" ^{}", pid));
+    }
+    for did in &task.depends {
+        suffix.push_str(&format!(" @{}", did));
+    }
+    if task.priority != 0 {
+        suffix.push_str(&format!(" !{}", task.priority));
+    }
+    for label in &task.labels {
+        suffix.push_str(&format!(" #{}", label));
+    }
+    if let Some(agent) = &task.assignee {
+        suffix.push_str(&format!(" <{}>", agent));
+    }
+    if let Some(progress) = task.progress {
+        suffix.push_str(&format!(" ({}/{} done)", progress.done, progress.total));
+    }
+
+    format!(
+        "{}  [{}]  {}{}",
+        task.id,
+        workflow.marker(&task.status),
+        task.title,
+        suffix
+    )
+}
+
+fn cmd_update(conn: &Connection, id: &str, description: Option<&str>, priority: Option<i32>) -> Result<()> {
+    update_task(conn, id, description, priority)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "success": true, "id": id }));
+    }
+    println!("Updated: {}", id);
+    Ok(())
+}
+
+fn cmd_edit(conn: &Connection, id: &str, changes: &TaskEdit) -> Result<()> {
+    edit_task(conn, id, changes)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "success": true, "id": id }));
+    }
+    println!("Updated: {}", id);
+    Ok(())
+}
+
+fn cmd_start(conn: &Connection, id: &str, lease: Option<&str>) -> Result<()> {
+    start_task(conn, id, lease)?;
+    let lease_until = get_task(conn, id)?.lease_until;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "success": true,
+            "id": id,
+            "lease_until": lease_until
+        }));
+    }
+    match lease_until {
+        Some(until) => println!("Started: {} (lease until {})", id, until),
+        None => println!("Started: {}", id),
+    }
+    Ok(())
+}
+
+fn cmd_heartbeat(conn: &Connection, id: &str, agent: Option<&str>, lease: Option<&str>) -> Result<()> {
+    let agent = agent.map(str::to_string).unwrap_or_else(current_actor);
+    let until = heartbeat_task(conn, id, &agent, lease)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "success": true, "id": id, "lease_until": until }));
+    }
+    println!("Lease renewed: {} (until {})", id, until);
+    Ok(())
+}
+
+fn cmd_claim(conn: &Connection, id: Option<&str>, agent: Option<&str>, lease: Option<&str>) -> Result<()> {
+    let agent = agent.map(str::to_string).unwrap_or_else(current_actor);
+    let claimed = claim_task(conn, id, &agent, lease)?;
+    if json_output() {
+        let task = claimed.map(|id| get_task(conn, &id)).transpose()?;
+        return print_json(&task);
+    }
+    match claimed {
+        Some(id) => {
+            println!("Claimed: {} (as {})", id, agent);
+            println!();
+            print_task(get_task(conn, &id)?);
+        }
+        None => println!("No actionable tasks."),
+    }
+    Ok(())
+}
+
+fn cmd_done(conn: &Connection, id: &str) -> Result<()> {
+    let parents = complete_task(conn, id)?;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "success": true,
+            "id": id,
+            "auto_completed_parents": parents
+        }));
+    }
+    println!("Done: {}", id);
+    for parent in parents {
+        println!("Done: {} (all subtasks done)", parent);
+    }
+    Ok(())
+}
+
+fn cmd_reopen(conn: &Connection, id: &str) -> Result<()> {
+    let completed = reopen_task(conn, id)?;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "success": true,
+            "id": id,
+            "completed_dependents": completed
+        }));
+    }
+    println!("Reopened: {}", id);
+    if !completed.is_empty() {
+        eprintln!(
+            "Warning: already completed task(s) depend on '{}': {}",
+            id,
+            completed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn cmd_stop(conn: &Connection, id: &str) -> Result<()> {
+    stop_task(conn, id)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "success": true, "id": id }));
+    }
+    println!("Stopped: {}", id);
+    Ok(())
+}
+
+fn cmd_cancel(conn: &Connection, id: &str) -> Result<()> {
+    let (blocked, parents) = cancel_task(conn, id)?;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "success": true,
+            "id": id,
+            "blocked_dependents": blocked,
+            "auto_completed_parents": parents
+        }));
+    }
+    println!("Cancelled: {}", id);
+    for parent in parents {
+        println!("Done: {} (no open subtasks left)", parent);
+    }
+    if !blocked.is_empty() {
+        eprintln!(
+            "Warning: active task(s) depend on '{}' and stay blocked: {}",
+            id,
+            blocked.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn cmd_status(conn: &Connection, id: &str, state: &str) -> Result<()> {
+    let parents = set_status(conn, id, state)?;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "success": true,
+            "id": id,
+            "status": state,
+            "auto_completed_parents": parents
+        }));
+    }
+    println!("Moved: {} -> {}", id, state);
+    for parent in parents {
+        println!("Done: {} (all subtasks done)", parent);
+    }
+    Ok(())
+}
+
+fn cmd_workflow() -> Result<()> {
+    let workflow = workflow()?;
+    if json_output() {
+        return print_json(workflow);
+    }
+
+    println!("States:");
+    for state in &workflow.states {
+        println!("  [{}]  {:<14} ({})", state.marker, state.name, state.kind.as_str());
+    }
+
+    println!();
+    println!("Transitions:");
+    if workflow.transitions.is_empty() {
+        println!("  any state -> any state");
+    }
+    for (from, targets) in &workflow.transitions {
+        let targets = if targets.is_empty() { "-".to_string() } else { targets.join(", ") };
+        println!("  {:<14} -> {}", from, targets);
+    }
+    Ok(())
+}
+
+fn cmd_remove(
+    conn: &Connection,
+    id: &str,
+    children: ChildPolicy,
+    dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    // Removing a whole subtree is confirmed: asked in a terminal, else --yes
+    if children == ChildPolicy::Recursive && !dry_run && !yes {
+        let preview = remove_task(conn, id, children, true)?;
+        let count = preview.removed.len();
+        if count > 1 && preview.blocked.is_empty() {
+            if json_output() || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+                bail!(
+                    "This would remove '{}' and {} subtask(s). Pass --yes to confirm (--dry-run to preview).",
+                    id,
+                    count - 1
+                );
+            }
+            let workflow = workflow()?;
+            println!("This removes {} tasks:", count);
+            for task in &preview.removed {
+                println!("{}", task_line(workflow, task, true));
+            }
+            if !Confirm::new().with_prompt("Remove them?").default(false).interact()? {
+                println!("Nothing removed.");
+                return Ok(());
+            }
+        }
+    }
+
+    let removal = remove_task(conn, id, children, dry_run)?;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "success": true,
+            "id": id,
+            "dry_run": dry_run,
+            "removed": removal.removed,
+            "moved": removal.moved,
+            "new_parent": removal.new_parent,
+            "blocked": removal.blocked,
+        }));
+    }
+    let (moved, removed) = if dry_run {
+        ("Would move", "Would remove")
+    } else {
+        ("Moved", "Removed")
+    };
+
+    if !removal.moved.is_empty() {
+        let target = match &removal.new_parent {
+            Some(parent) => format!("under {}", parent),
+            None => "to top level".to_string(),
+        };
+        println!("{} {}: {}", moved, target, removal.moved.join(", "));
+    }
+
+    if removal.removed.len() == 1 {
+        println!("{}: {}", removed, id);
+    } else {
+        let workflow = workflow()?;
+        println!("{} {} tasks:", removed, removal.removed.len());
+        for task in &removal.removed {
+            println!("{}", task_line(workflow, task, true));
+        }
+    }
+
+    if !removal.blocked.is_empty() {
+        println!(
+            "Blocked: {} active task(s) depend on it and must be changed first: {}",
+            removal.blocked.len(),
+            removal.blocked.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn cmd_depend_add(conn: &Connection, id: &str, depend: &str) -> Result<()> {
+    add_dependency(conn, id, depend)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "success": true, "id": id, "depend": depend }));
+    }
+    println!("Added: {} @{}", id, depend);
+    Ok(())
+}
+
+fn cmd_depend_remove(conn: &Connection, id: &str, depend: &str) -> Result<()> {
+    remove_dependency(conn, id, depend)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "success": true, "id": id, "depend": depend }));
+    }
+    println!("Removed: {} @{}", id, depend);
+    Ok(())
+}
+
+fn cmd_label_add(conn: &Connection, id: &str, label: &str) -> Result<()> {
+    let label = add_label(conn, id, label)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "success": true, "id": id, "label": label }));
+    }
+    println!("Added: {} #{}", id, label);
+    Ok(())
+}
+
+fn cmd_label_remove(conn: &Connection, id: &str, label: &str) -> Result<()> {
+    let label = remove_label(conn, id, label)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "success": true, "id": id, "label": label }));
+    }
+    println!("Removed: {} #{}", id, label);
+    Ok(())
+}
+
+fn print_task(task: Task) {
+    println!("ID:          {}", task.id);
+    println!("Title:       {}", task.title);
+    println!("Status:      {}", task.status);
+    if task.priority != 0 {
+        println!("Priority:    {}", task.priority);
+    }
+    if let Some(pid) = task.parent_id {
+        println!("Parent:      {}", pid);
+    }
+    if let Some(progress) = task.progress {
+        println!("Subtasks:    {}/{} done", progress.done, progress.total);
+    }
+    if !task.depends.is_empty() {
+        println!("Depends on:  {}", task.depends.join(", "));
+    }
+    if !task.labels.is_empty() {
+        println!("Labels:      {}", task.labels.join(", "));
+    }
+    if let Some(agent) = task.assignee {
+        println!("Assignee:    {}", agent);
+    }
+    if let Some(started) = task.started_at {
+        println!("Started:     {}", started);
+    }
+    if let Some(until) = task.lease_until {
+        println!("Lease until: {}", until);
+    }
+    if let Some(due) = task.due {
+        let note = if task.overdue { " (overdue)" } else { "" };
+        println!("Due:         {}{}", due, note);
+    }
+    if let Some(created) = task.created_at {
+        println!("Created:     {}", created);
+    }
+    println!();
+    println!("{}", task.description);
+
+    if !task.memories.is_empty() {
+        println!();
+        println!("Memories:");
+        for mem in &task.memories {
+            println!(
+                "  [{}] {}{}{}",
+                mem.id,
+                memory_prefix(mem),
+                truncate_content(&mem.content, 60),
+                tags_suffix(&mem.tags)
+            );
+        }
+    }
 
-        let selections = MultiSelect::new()
-            .items(&agents)
-            .interact_opt()?;
+    if !task.comments.is_empty() {
+        println!();
+        println!("Comments:");
+        print_comments(&task.comments);
+    }
+}
 
-        if let Some(selected) = selections {
-            if !selected.is_empty() {
-                println!();
-                install_agent_rules(&current_dir, &selected)?;
-                println!();
-                println!("Agent rules installed.");
-            }
+fn print_comments(comments: &[TaskComment]) {
+    for comment in comments {
+        println!();
+        println!("  {}  {}", comment.created_at, comment.author);
+        for line in comment.text.lines() {
+            println!("  {}", line);
         }
-    } else {
-        println!("Already initialized. Use --rules to add agent rules.");
     }
-
-    Ok(())
 }
 
-fn cmd_create(
-    conn: &Connection,
-    title: &str,
-    description: &str,
-    parent: Option<&str>,
-    depend: Option<&str>,
-) -> Result<()> {
-    let id = create_task(conn, title, description, parent, depend)?;
-    println!("{}", id);
+fn cmd_comment(conn: &Connection, id: &str, text: &str) -> Result<()> {
+    let comment_id = add_comment(conn, id, text)?;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "success": true,
+            "id": id,
+            "comment_id": comment_id
+        }));
+    }
+    println!("Commented: {}", id);
     Ok(())
 }
 
-fn cmd_list(conn: &Connection, inprogress: bool, all: bool, parent: Option<&str>) -> Result<()> {
-    let tasks = list_tasks(conn, inprogress, all, parent)?;
+fn cmd_comments(conn: &Connection, id: &str) -> Result<()> {
+    validate_id(id)?;
+    if !task_exists(conn, id)? {
+        bail!("Task '{}' not found.", id);
+    }
 
-    for task in tasks {
-        let mut suffix = String::new();
-        if let Some(pid) = task.parent_id {
-            suffix.push_str(&format!(" ^{}", pid));
-        }
-        if let Some(did) = task.depend_id {
-            suffix.push_str(&format!(" @{}", did));
-        }
+    let comments = task_comments(conn, id)?;
+    if json_output() {
+        return print_json(&comments);
+    }
+    if comments.is_empty() {
+        println!("No comments.");
+        return Ok(());
+    }
+    print_comments(&comments);
+    Ok(())
+}
 
-        println!(
-            "{}  [{}]  {}{}",
-            task.id,
-            task.status.marker(),
-            task.title,
-            suffix
-        );
+fn cmd_link(conn: &Connection, task: &str, memory: &str) -> Result<()> {
+    link_memory(conn, task, memory)?;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "success": true,
+            "task_id": task,
+            "memory_id": memory
+        }));
     }
+    println!("Linked: {} -> {}", task, memory);
+    Ok(())
+}
 
+fn cmd_unlink(conn: &Connection, task: &str, memory: &str) -> Result<()> {
+    unlink_memory(conn, task, memory)?;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "success": true,
+            "task_id": task,
+            "memory_id": memory
+        }));
+    }
+    println!("Unlinked: {} -> {}", task, memory);
     Ok(())
 }
 
-fn cmd_update(conn: &Connection, id: &str, description: &str) -> Result<()> {
-    update_task(conn, id, description)?;
-    println!("Updated: {}", id);
+fn cmd_show(conn: &Connection, id: &str) -> Result<()> {
+    let task = get_task(conn, id)?;
+    if json_output() {
+        return print_json(&task);
+    }
+    print_task(task);
     Ok(())
 }
 
-fn cmd_start(conn: &Connection, id: &str) -> Result<()> {
-    start_task(conn, id)?;
-    println!("Started: {}", id);
+fn cmd_next(conn: &Connection) -> Result<()> {
+    let task = next_task(conn)?;
+    if json_output() {
+        return print_json(&task);
+    }
+    match task {
+        Some(task) => print_task(task),
+        None => println!("No actionable tasks."),
+    }
     Ok(())
 }
 
-fn cmd_done(conn: &Connection, id: &str) -> Result<()> {
-    complete_task(conn, id)?;
-    println!("Done: {}", id);
+fn cmd_log(conn: &Connection, id: Option<&str>, last: Option<usize>) -> Result<()> {
+    let events = task_history(conn, id, last)?;
+    if json_output() {
+        return print_json(&events);
+    }
+
+    for event in events {
+        let change = match (&event.old_value, &event.new_value) {
+            (Some(old), Some(new)) => format!("{} -> {}", truncate_content(old, 30), truncate_content(new, 30)),
+            (None, Some(new)) => format!("+ {}", truncate_content(new, 50)),
+            (Some(old), None) => format!("- {}", truncate_content(old, 50)),
+            (None, None) => String::new(),
+        };
+        let field = event.field.map(|f| format!("{}: ", f)).unwrap_or_default();
+
+        println!(
+            "{}  {}  {:<9}  {}{}  ({})",
+            event.created_at, event.task_id, event.event, field, change, event.actor
+        );
+    }
+
     Ok(())
 }
 
-fn cmd_remove(conn: &Connection, id: &str) -> Result<()> {
-    remove_task(conn, id)?;
-    println!("Removed: {}", id);
+fn cmd_undo(conn: &Connection, count: usize, dry_run: bool) -> Result<()> {
+    let entries = undo(conn, count, dry_run)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "dry_run": dry_run, "undone": entries }));
+    }
+    let verb = if dry_run { "Would undo" } else { "Undone" };
+
+    for entry in &entries {
+        println!("{}: {}  ({}, {})", verb, entry.operation, entry.actor, entry.created_at);
+    }
+    if entries.len() < count {
+        println!("No earlier changes recorded.");
+    }
     Ok(())
 }
 
-fn cmd_show(conn: &Connection, id: &str) -> Result<()> {
-    let task = get_task(conn, id)?;
+fn cmd_doctor(conn: &Connection, fix: bool) -> Result<()> {
+    let issues = doctor(conn, fix)?;
 
-    println!("ID:          {}", task.id);
-    println!("Title:       {}", task.title);
-    println!("Status:      {}", task.status.as_str());
-    if let Some(pid) = task.parent_id {
-        println!("Parent:      {}", pid);
+    if json_output() {
+        return print_json(&issues);
     }
-    if let Some(did) = task.depend_id {
-        println!("Depends on:  {}", did);
+
+    if issues.is_empty() {
+        println!("No issues found.");
+        return Ok(());
     }
-    if let Some(created) = task.created_at {
-        println!("Created:     {}", created);
+
+    for issue in &issues {
+        let status = if issue.fixed { "fixed" } else { "found" };
+        println!("{:<18} {}  [{}]  {}", issue.kind.as_str(), issue.id, status, issue.detail);
     }
     println!();
-    println!("{}", task.description);
+    if fix {
+        println!("{} issue(s) fixed.", issues.len());
+    } else {
+        println!("{} issue(s) found. Run 'tsk doctor --fix' to repair.", issues.len());
+    }
     Ok(())
 }
 
 fn cmd_ids(conn: &Connection) -> Result<()> {
     let ids = get_task_ids(conn)?;
+    if json_output() {
+        return print_json(&ids);
+    }
     for id in ids {
         println!("{}", id);
     }
@@ -1106,41 +5949,229 @@ fn truncate_content(s: &str, max_len: usize) -> String {
     }
 }
 
-fn cmd_memory_create(conn: &Connection, content: &str, tags: Option<&str>) -> Result<()> {
-    let id = create_memory(conn, content, tags)?;
-    println!("{}", id);
+fn cmd_memory_create(conn: &Connection, memory: &NewMemory) -> Result<()> {
+    let created = create_memory(conn, memory)?;
+    if json_output() {
+        return print_json(&created);
+    }
+    println!("{}", created.id);
+    warn_similar(&created.similar);
     Ok(())
 }
 
-fn cmd_memory_list(conn: &Connection, tag: Option<&str>, last: Option<usize>) -> Result<()> {
-    let memories = list_memories(conn, tag, last)?;
+fn cmd_memory_list(conn: &Connection, filter: &MemoryFilter) -> Result<()> {
+    let memories = list_memories(conn, filter)?;
+    if json_output() {
+        return print_json(&memories);
+    }
 
     for mem in memories {
         let content_preview = truncate_content(&mem.content, 50);
-        let tags_str = mem.tags.map(|t| format!(" [{}]", t)).unwrap_or_default();
-        println!("[{}] {}{}", mem.id, content_preview, tags_str);
+        let tags_str = tags_suffix(&mem.tags);
+        println!("[{}] {}{}{}", mem.id, memory_prefix(&mem), content_preview, tags_str);
+    }
+
+    Ok(())
+}
+
+fn memory_prefix(memory: &Memory) -> String {
+    let mut labels: Vec<&str> = memory.kind.map(MemoryKind::as_str).into_iter().collect();
+    if memory.pinned {
+        labels.push("pinned");
+    }
+    if memory.stale {
+        labels.push("stale");
+    }
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("({}) ", labels.join(", "))
+    }
+}
+
+fn tags_suffix(tags: &[String]) -> String {
+    if tags.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", tags.join(","))
+    }
+}
+
+fn cmd_memory_tags(conn: &Connection) -> Result<()> {
+    let tags = memory_tags(conn)?;
+    if json_output() {
+        return print_json(&tags);
+    }
+
+    if tags.is_empty() {
+        println!("No tags.");
+        return Ok(());
+    }
+
+    let width = tags.iter().map(|t| t.tag.len()).max().unwrap_or(0);
+    for tag in tags {
+        println!("{:<width$}  {}", tag.tag, tag.count, width = width);
+    }
+    Ok(())
+}
+
+fn cmd_memory_review(conn: &Connection) -> Result<()> {
+    if json_output() {
+        bail!("'tsk m review' is interactive. Use 'tsk m list --stale --json' to see stale entries.");
+    }
+    let filter = MemoryFilter {
+        stale: true,
+        ..Default::default()
+    };
+    let mut memories = list_memories(conn, &filter)?;
+    memories.reverse();
+
+    if memories.is_empty() {
+        println!("No stale memories.");
+        return Ok(());
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        bail!("'tsk m review' needs a terminal. Use 'tsk m list --stale' to see stale entries.");
+    }
+
+    let today: String = conn.query_row("SELECT date('now', 'localtime')", [], |row| row.get(0))?;
+    let (mut kept, mut removed) = (0, 0);
+    for (i, memory) in memories.iter().enumerate() {
+        println!();
+        println!("[{}/{}] {}{}", i + 1, memories.len(), memory_prefix(memory), memory.id);
+        if let Some(review) = &memory.review_after {
+            println!("Review:  {}", review);
+        }
+        if let Some(expires) = &memory.expires_at {
+            println!("Expires: {}", expires);
+        }
+        println!();
+        println!("{}", memory.content);
+        println!();
+
+        let action = Select::new()
+            .with_prompt("Still true?")
+            .items(&["Keep", "Edit", "Delete", "Skip", "Quit"])
+            .default(0)
+            .interact()?;
+        match action {
+            0 | 1 => {
+                let content = if action == 1 {
+                    Editor::new().extension(".md").edit(&memory.content)?
+                } else {
+                    None
+                };
+                let next: String = Input::new()
+                    .with_prompt("Review again after (empty: never)")
+                    .default("+90d".to_string())
+                    .allow_empty(true)
+                    .interact_text()?;
+                let expired = memory.expires_at.as_ref().is_some_and(|d| *d <= today);
+                let changes = MemoryEdit {
+                    content: content.as_deref().map(str::trim_end),
+                    review_after: Some(Some(next.trim()).filter(|d| !d.is_empty())),
+                    // Kept past its expiry date, so it still holds
+                    expires_at: expired.then_some(None),
+                    ..Default::default()
+                };
+                edit_memory(conn, &memory.id, &changes)?;
+                kept += 1;
+            }
+            2 => {
+                remove_memory(conn, &memory.id)?;
+                removed += 1;
+            }
+            3 => {}
+            _ => break,
+        }
+    }
+
+    println!();
+    println!("Reviewed: {} kept, {} removed", kept, removed);
+    Ok(())
+}
+
+fn cmd_memory_edit(conn: &Connection, id: &str, changes: &MemoryEdit) -> Result<()> {
+    if !changes.is_empty() || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        edit_memory(conn, id, changes)?;
+        if json_output() {
+            return print_json(&get_memory(conn, id)?);
+        }
+        println!("Updated: {}", id);
+        return Ok(());
     }
 
+    let memory = get_memory(conn, id)?;
+    let Some(edited) = Editor::new().extension(".md").edit(&memory.content)? else {
+        if json_output() {
+            return print_json(&memory);
+        }
+        println!("Not saved: {} unchanged", id);
+        return Ok(());
+    };
+    let changes = MemoryEdit {
+        content: Some(edited.trim_end()),
+        ..Default::default()
+    };
+    edit_memory(conn, id, &changes)?;
+    if json_output() {
+        return print_json(&get_memory(conn, id)?);
+    }
+    println!("Updated: {}", id);
     Ok(())
 }
 
 fn cmd_memory_show(conn: &Connection, id: &str) -> Result<()> {
     let mem = get_memory(conn, id)?;
+    if json_output() {
+        return print_json(&mem);
+    }
 
     println!("ID:      {}", mem.id);
-    if let Some(tags) = mem.tags {
-        println!("Tags:    {}", tags);
+    if let Some(kind) = mem.kind {
+        println!("Kind:    {}", kind.as_str());
+    }
+    if mem.pinned {
+        println!("Pinned:  yes");
+    }
+    if !mem.tags.is_empty() {
+        println!("Tags:    {}", mem.tags.join(", "));
     }
     if let Some(created) = mem.created_at {
         println!("Created: {}", created);
     }
+    if let Some(updated) = mem.updated_at {
+        println!("Updated: {}", updated);
+    }
+    if let Some(review) = mem.review_after {
+        println!("Review:  {}", review);
+    }
+    if let Some(expires) = mem.expires_at {
+        println!("Expires: {}", expires);
+    }
+    if mem.stale {
+        println!("Stale:   yes (see 'tsk m review')");
+    }
     println!();
     println!("{}", mem.content);
+
+    if !mem.tasks.is_empty() {
+        let workflow = workflow()?;
+        println!();
+        println!("Tasks:");
+        for task_id in &mem.tasks {
+            let task = get_task(conn, task_id)?;
+            println!("  {}  [{}]  {}", task.id, workflow.marker(&task.status), task.title);
+        }
+    }
     Ok(())
 }
 
 fn cmd_memory_search(conn: &Connection, query: &str) -> Result<()> {
     let memories = search_memories(conn, query)?;
+    if json_output() {
+        return print_json(&memories);
+    }
 
     if memories.is_empty() {
         println!("No matches found.");
@@ -1149,15 +6180,45 @@ fn cmd_memory_search(conn: &Connection, query: &str) -> Result<()> {
 
     for mem in memories {
         let content_preview = truncate_content(&mem.content, 50);
-        let tags_str = mem.tags.map(|t| format!(" [{}]", t)).unwrap_or_default();
-        println!("[{}] {}{}", mem.id, content_preview, tags_str);
+        let tags_str = tags_suffix(&mem.tags);
+        println!("[{}] {}{}{}", mem.id, memory_prefix(&mem), content_preview, tags_str);
+    }
+
+    Ok(())
+}
+
+fn cmd_memory_pin(conn: &Connection, id: &str, pinned: bool) -> Result<()> {
+    pin_memory(conn, id, pinned)?;
+    if json_output() {
+        return print_json(&get_memory(conn, id)?);
+    }
+    println!("{}: {}", if pinned { "Pinned" } else { "Unpinned" }, id);
+    Ok(())
+}
+
+fn cmd_memory_export(conn: &Connection, dir: &Path) -> Result<()> {
+    let paths = export_adr(conn, dir)?;
+    if json_output() {
+        return print_json(&paths);
     }
 
+    if paths.is_empty() {
+        println!("No decisions to export.");
+        return Ok(());
+    }
+
+    for path in &paths {
+        println!("{}", path.display());
+    }
+    println!("Exported {} decision(s) to {}", paths.len(), dir.display());
     Ok(())
 }
 
 fn cmd_memory_remove(conn: &Connection, id: &str) -> Result<()> {
     remove_memory(conn, id)?;
+    if json_output() {
+        return print_json(&serde_json::json!({ "success": true, "id": id }));
+    }
     println!("Removed: {}", id);
     Ok(())
 }
@@ -1252,9 +6313,53 @@ fn cmd_selfupdate() -> Result<()> {
     Ok(())
 }
 
+/// Whether `--json` or `--jsonl` was given, as clap parses it (so a
+/// positional value spelled `--json` doesn't count), even when the rest of
+/// the command line is invalid
+fn json_requested() -> bool {
+    let Ok(matches) = Cli::command().ignore_errors(true).try_get_matches() else {
+        return false;
+    };
+    ["json", "jsonl"]
+        .iter()
+        .any(|id| matches.try_get_one::<bool>(id).ok().flatten() == Some(&true))
+}
+
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) if e.use_stderr() && json_requested() => {
+            // clap's message without the usage block and --help hint
+            let message = e.to_string();
+            let message = message
+                .lines()
+                .take_while(|line| !line.starts_with("Usage:") && !line.starts_with("For more information"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let message = message.trim().trim_start_matches("error: ");
+            println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "error": message }))?);
+            std::process::exit(2);
+        }
+        Err(e) => e.exit(),
+    };
+
+    let output = match (cli.json, cli.jsonl) {
+        (_, true) => Output::Jsonl,
+        (true, false) => Output::Json,
+        (false, false) => Output::Text,
+    };
+    let _ = OUTPUT.set(output);
+
+    match run(cli) {
+        Err(e) if output != Output::Text => {
+            println!("{}", serde_json::to_string_pretty(&error_json(&e))?);
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
 
+fn run(cli: Cli) -> Result<()> {
     // Handle --selfupdate before subcommands
     if cli.selfupdate {
         return cmd_selfupdate();
@@ -1276,13 +6381,12 @@ fn main() -> Result<()> {
             let Some(db_path) = db_path else {
                 // For ids command, just return empty if not initialized
                 if matches!(cmd, Commands::Ids) {
-                    return Ok(());
+                    return if json_output() { print_json(&Vec::<String>::new()) } else { Ok(()) };
                 }
                 bail!("Project not initialized. Run 'tsk init' first.");
             };
 
-            let conn = Connection::open(&db_path)?;
-            migrate_db(&conn)?;
+            let conn = connect(&db_path)?;
 
             match cmd {
                 Commands::Init { .. } => unreachable!(),
@@ -1293,34 +6397,253 @@ fn main() -> Result<()> {
                     description,
                     parent,
                     depend,
+                    priority,
+                    label,
+                    due,
+                    force,
+                    unique,
                 } => {
-                    cmd_create(&conn, &title, &description, parent.as_deref(), depend.as_deref())?;
+                    let task = NewTask {
+                        title: &title,
+                        description: &description,
+                        parent: parent.as_deref(),
+                        depends: &depend,
+                        priority,
+                        labels: &label,
+                        due: due.as_deref(),
+                        duplicates: DuplicatePolicy::from_flags(force, unique),
+                    };
+                    cmd_create(&conn, &task)?;
                 }
-                Commands::List { inprogress, all, parent } => {
-                    cmd_list(&conn, inprogress, all, parent.as_deref())?;
+                Commands::List {
+                    inprogress,
+                    all,
+                    parent,
+                    status,
+                    label,
+                    overdue,
+                    due_before,
+                    mine,
+                } => {
+                    let me = current_actor();
+                    let filter = TaskFilter {
+                        inprogress,
+                        all,
+                        parent: parent.as_deref(),
+                        status: status.as_deref(),
+                        label: label.as_deref(),
+                        overdue,
+                        due_before: due_before.as_deref(),
+                        assignee: mine.then_some(me.as_str()),
+                    };
+                    cmd_list(&conn, &filter)?;
+                }
+                Commands::Update { id, description, priority } => {
+                    cmd_update(&conn, &id, description.as_deref(), priority)?;
+                }
+                Commands::Edit {
+                    id,
+                    title,
+                    description,
+                    priority,
+                    parent,
+                    no_parent,
+                    depend,
+                    no_depend,
+                    due,
+                    no_due,
+                } => {
+                    let changes = TaskEdit {
+                        title: title.as_deref(),
+                        description: description.as_deref(),
+                        priority,
+                        parent: if no_parent { Some(None) } else { parent.as_deref().map(Some) },
+                        depends: if no_depend || !depend.is_empty() {
+                            Some(&depend)
+                        } else {
+                            None
+                        },
+                        due: if no_due { Some(None) } else { due.as_deref().map(Some) },
+                    };
+                    cmd_edit(&conn, &id, &changes)?;
+                }
+                Commands::Next => {
+                    cmd_next(&conn)?;
+                }
+                Commands::Claim { id, agent, lease } => {
+                    cmd_claim(&conn, id.as_deref(), agent.as_deref(), lease.as_deref())?;
+                }
+                Commands::Tree { root, depth, all } => {
+                    cmd_tree(&conn, root.as_deref(), depth, all)?;
+                }
+                Commands::Agenda => {
+                    cmd_agenda(&conn)?;
+                }
+                Commands::Context { budget } => {
+                    cmd_context(&conn, budget)?;
+                }
+                Commands::Search {
+                    query,
+                    kind,
+                    status,
+                    limit,
+                } => {
+                    cmd_search(&conn, &query, kind, status.as_deref(), limit)?;
                 }
-                Commands::Update { id, description } => {
-                    cmd_update(&conn, &id, &description)?;
+                Commands::Start { id, lease } => {
+                    cmd_start(&conn, &id, lease.as_deref())?;
                 }
-                Commands::Start { id } => {
-                    cmd_start(&conn, &id)?;
+                Commands::Heartbeat { id, agent, lease } => {
+                    cmd_heartbeat(&conn, &id, agent.as_deref(), lease.as_deref())?;
                 }
                 Commands::Done { id } => {
                     cmd_done(&conn, &id)?;
                 }
-                Commands::Remove { id } => {
-                    cmd_remove(&conn, &id)?;
+                Commands::Reopen { id } => {
+                    cmd_reopen(&conn, &id)?;
+                }
+                Commands::Stop { id } => {
+                    cmd_stop(&conn, &id)?;
+                }
+                Commands::Cancel { id } => {
+                    cmd_cancel(&conn, &id)?;
+                }
+                Commands::Status { id, state } => {
+                    cmd_status(&conn, &id, &state)?;
+                }
+                Commands::Workflow => {
+                    cmd_workflow()?;
+                }
+                Commands::Remove {
+                    id,
+                    recursive,
+                    orphan_children,
+                    reparent_to,
+                    dry_run,
+                    yes,
+                } => {
+                    let children = if recursive {
+                        ChildPolicy::Recursive
+                    } else if orphan_children {
+                        ChildPolicy::Orphan
+                    } else if let Some(to) = reparent_to.as_deref() {
+                        ChildPolicy::Reparent(to)
+                    } else {
+                        ChildPolicy::Refuse
+                    };
+                    cmd_remove(&conn, &id, children, dry_run, yes)?;
                 }
                 Commands::Show { id } => {
                     cmd_show(&conn, &id)?;
                 }
+                Commands::Depend { action } => match action {
+                    DependCommands::Add { id, depend } => {
+                        cmd_depend_add(&conn, &id, &depend)?;
+                    }
+                    DependCommands::Rm { id, depend } => {
+                        cmd_depend_remove(&conn, &id, &depend)?;
+                    }
+                },
+                Commands::Label { action } => match action {
+                    LabelCommands::Add { id, label } => {
+                        cmd_label_add(&conn, &id, &label)?;
+                    }
+                    LabelCommands::Rm { id, label } => {
+                        cmd_label_remove(&conn, &id, &label)?;
+                    }
+                },
+                Commands::Log { id, all: _, last } => {
+                    cmd_log(&conn, id.as_deref(), last)?;
+                }
+                Commands::Undo { count, dry_run } => {
+                    cmd_undo(&conn, count, dry_run)?;
+                }
+                Commands::Comment { id, text } => {
+                    cmd_comment(&conn, &id, &text)?;
+                }
+                Commands::Comments { id } => {
+                    cmd_comments(&conn, &id)?;
+                }
+                Commands::Link { task, memory } => {
+                    cmd_link(&conn, &task, &memory)?;
+                }
+                Commands::Unlink { task, memory } => {
+                    cmd_unlink(&conn, &task, &memory)?;
+                }
+                Commands::Doctor { fix } => {
+                    cmd_doctor(&conn, fix)?;
+                }
                 Commands::Ids => {
                     cmd_ids(&conn)?;
                 }
-                Commands::M { action, content, tags } => {
+                Commands::M {
+                    action,
+                    content,
+                    tags,
+                    task,
+                    kind,
+                    review_after,
+                    expires,
+                    force,
+                    unique,
+                } => {
                     match action {
-                        Some(MemoryCommands::List { tag, last }) => {
-                            cmd_memory_list(&conn, tag.as_deref(), last)?;
+                        Some(MemoryCommands::List {
+                            tag,
+                            any,
+                            kind,
+                            stale,
+                            last,
+                        }) => {
+                            let filter = MemoryFilter {
+                                tags: &tag,
+                                any,
+                                kind,
+                                stale,
+                                last,
+                            };
+                            cmd_memory_list(&conn, &filter)?;
+                        }
+                        Some(MemoryCommands::Tags) => {
+                            cmd_memory_tags(&conn)?;
+                        }
+                        Some(MemoryCommands::Review) => {
+                            cmd_memory_review(&conn)?;
+                        }
+                        Some(MemoryCommands::Edit {
+                            id,
+                            content,
+                            kind: new_kind,
+                            no_kind,
+                            review_after: new_review_after,
+                            no_review_after,
+                            expires: new_expires,
+                            no_expires,
+                            tags,
+                            add_tag,
+                            rm_tag,
+                        }) => {
+                            // `--tags ""` clears the tags like a bare `--tags`
+                            let tags: Option<Vec<String>> =
+                                tags.map(|t| t.into_iter().filter(|t| !t.trim().is_empty()).collect());
+                            let changes = MemoryEdit {
+                                content: content.as_deref(),
+                                kind: if no_kind { Some(None) } else { new_kind.map(Some) },
+                                review_after: if no_review_after {
+                                    Some(None)
+                                } else {
+                                    new_review_after.as_deref().map(Some)
+                                },
+                                expires_at: if no_expires {
+                                    Some(None)
+                                } else {
+                                    new_expires.as_deref().map(Some)
+                                },
+                                tags: tags.as_deref(),
+                                add_tags: &add_tag,
+                                remove_tags: &rm_tag,
+                            };
+                            cmd_memory_edit(&conn, &id, &changes)?;
                         }
                         Some(MemoryCommands::Show { id }) => {
                             cmd_memory_show(&conn, &id)?;
@@ -1328,12 +6651,30 @@ fn main() -> Result<()> {
                         Some(MemoryCommands::Search { query }) => {
                             cmd_memory_search(&conn, &query)?;
                         }
+                        Some(MemoryCommands::Pin { id }) => {
+                            cmd_memory_pin(&conn, &id, true)?;
+                        }
+                        Some(MemoryCommands::Unpin { id }) => {
+                            cmd_memory_pin(&conn, &id, false)?;
+                        }
+                        Some(MemoryCommands::Export { adr }) => {
+                            cmd_memory_export(&conn, &adr)?;
+                        }
                         Some(MemoryCommands::Rm { id }) => {
                             cmd_memory_remove(&conn, &id)?;
                         }
                         None => {
                             if let Some(text) = content {
-                                cmd_memory_create(&conn, &text, tags.as_deref())?;
+                                let memory = NewMemory {
+                                    content: &text,
+                                    kind,
+                                    tags: &tags,
+                                    review_after: review_after.as_deref(),
+                                    expires_at: expires.as_deref(),
+                                    duplicates: DuplicatePolicy::from_flags(force, unique),
+                                    tasks: &task,
+                                };
+                                cmd_memory_create(&conn, &memory)?;
                             } else {
                                 // Show help for m command
                                 Cli::parse_from(["tsk", "m", "--help"]);
@@ -1351,3 +6692,211 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh in-memory database with the current schema; with no
+    /// .tsk/config.json in the working directory the default workflow applies
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        migrate_db(&conn).unwrap();
+        conn
+    }
+
+    fn add(conn: &Connection, title: &str, parent: Option<&str>) -> String {
+        let task = NewTask {
+            title,
+            description: "",
+            parent,
+            ..Default::default()
+        };
+        create_task(conn, &task).unwrap().id
+    }
+
+    fn days_from_today(conn: &Connection, days: i64) -> String {
+        conn.query_row(
+            "SELECT date('now', 'localtime', ?1)",
+            [format!("+{} days", days)],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_due_accepts_dates_and_offsets() {
+        let conn = test_db();
+        assert_eq!(parse_due(&conn, "2026-02-28").unwrap(), "2026-02-28");
+        assert_eq!(parse_due(&conn, " Today ").unwrap(), days_from_today(&conn, 0));
+        assert_eq!(parse_due(&conn, "tomorrow").unwrap(), days_from_today(&conn, 1));
+        assert_eq!(parse_due(&conn, "+3d").unwrap(), days_from_today(&conn, 3));
+        assert_eq!(parse_due(&conn, "+2W").unwrap(), days_from_today(&conn, 14));
+        assert_eq!(parse_due(&conn, "+3650d").unwrap(), days_from_today(&conn, 3650));
+    }
+
+    #[test]
+    fn parse_due_rejects_bad_input() {
+        let conn = test_db();
+        for input in ["2026-02-30", "2026-13-01", "26-01-01", "", "+", "+d", "+3", "+3x", "+é"] {
+            assert!(parse_due(&conn, input).is_err(), "accepted {:?}", input);
+        }
+        for input in ["+-3d", "++3d"] {
+            let err = parse_due(&conn, input).unwrap_err().to_string();
+            assert!(err.contains("count forward"), "{}: {}", input, err);
+        }
+        for input in ["+3651d", "+522w", "+9223372036854775807w"] {
+            let err = parse_due(&conn, input).unwrap_err().to_string();
+            assert!(err.contains("too far ahead"), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn parse_lease_units_and_limits() {
+        assert_eq!(parse_lease("90s").unwrap(), 90);
+        assert_eq!(parse_lease(" 30m ").unwrap(), 1800);
+        assert_eq!(parse_lease("2h").unwrap(), 7200);
+        assert_eq!(parse_lease("30d").unwrap(), MAX_LEASE_SECONDS);
+
+        for input in ["", "5", "m", "0s", "-5m", "1.5h", "2w", "1é", "9223372036854775807d"] {
+            assert!(parse_lease(input).is_err(), "accepted {:?}", input);
+        }
+        assert!(parse_lease("31d").unwrap_err().to_string().contains("too long"));
+    }
+
+    #[test]
+    fn undo_restores_an_edit() {
+        let conn = test_db();
+        let id = add(&conn, "Original title", None);
+        let changes = TaskEdit {
+            title: Some("New title"),
+            priority: Some(3),
+            ..Default::default()
+        };
+        edit_task(&conn, &id, &changes).unwrap();
+
+        let undone = undo(&conn, 1, false).unwrap();
+        assert_eq!(undone[0].operation, format!("edit {}", id));
+        let task = get_task(&conn, &id).unwrap();
+        assert_eq!(task.title, "Original title");
+        assert_eq!(task.priority, 0);
+    }
+
+    #[test]
+    fn undo_restores_a_recursive_removal() {
+        let conn = test_db();
+        let root = add(&conn, "Root", None);
+        let child = add(&conn, "Child", Some(&root));
+        let grandchild = add(&conn, "Grandchild", Some(&child));
+
+        remove_task(&conn, &root, ChildPolicy::Recursive, false).unwrap();
+        assert!(!task_exists(&conn, &grandchild).unwrap());
+
+        undo(&conn, 1, false).unwrap();
+        assert_eq!(get_task(&conn, &child).unwrap().parent_id.as_deref(), Some(root.as_str()));
+        let grandchild_parent = get_task(&conn, &grandchild).unwrap().parent_id;
+        assert_eq!(grandchild_parent.as_deref(), Some(child.as_str()));
+        assert_eq!(subtree_ids(&conn, &root).unwrap(), [root, child, grandchild]);
+    }
+
+    #[test]
+    fn undo_refuses_after_untracked_changes() {
+        let conn = test_db();
+        let id = add(&conn, "Original title", None);
+        let changes = TaskEdit {
+            title: Some("New title"),
+            ..Default::default()
+        };
+        edit_task(&conn, &id, &changes).unwrap();
+        conn.execute("UPDATE tasks SET assignee = 'someone' WHERE id = ?1", [&id]).unwrap();
+
+        let err = undo(&conn, 1, false).unwrap_err().to_string();
+        assert!(err.contains("was changed since"), "{}", err);
+        assert_eq!(get_task(&conn, &id).unwrap().title, "New title");
+    }
+
+    #[test]
+    fn undo_needs_a_journal_entry() {
+        let conn = test_db();
+        assert!(undo(&conn, 1, false).is_err());
+        assert!(undo(&conn, 0, false).is_err());
+    }
+
+    #[test]
+    fn remove_refuses_tasks_with_subtasks_by_default() {
+        let conn = test_db();
+        let root = add(&conn, "Root", None);
+        let child = add(&conn, "Child", Some(&root));
+
+        assert!(remove_task(&conn, &root, ChildPolicy::Refuse, false).is_err());
+        assert!(task_exists(&conn, &root).unwrap());
+
+        let removal = remove_task(&conn, &child, ChildPolicy::Refuse, false).unwrap();
+        assert_eq!(removal.removed.len(), 1);
+        assert!(!task_exists(&conn, &child).unwrap());
+    }
+
+    #[test]
+    fn remove_recursive_takes_the_subtree() {
+        let conn = test_db();
+        let root = add(&conn, "Root", None);
+        let child = add(&conn, "Child", Some(&root));
+        let grandchild = add(&conn, "Grandchild", Some(&child));
+        let other = add(&conn, "Other", None);
+
+        let preview = remove_task(&conn, &root, ChildPolicy::Recursive, true).unwrap();
+        let ids: Vec<&str> = preview.removed.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, [root.as_str(), child.as_str(), grandchild.as_str()]);
+        assert!(task_exists(&conn, &grandchild).unwrap());
+
+        remove_task(&conn, &root, ChildPolicy::Recursive, false).unwrap();
+        for id in [&root, &child, &grandchild] {
+            assert!(!task_exists(&conn, id).unwrap());
+        }
+        assert!(task_exists(&conn, &other).unwrap());
+    }
+
+    #[test]
+    fn remove_orphan_and_reparent_move_the_children() {
+        let conn = test_db();
+        let root = add(&conn, "Root", None);
+        let child = add(&conn, "Child", Some(&root));
+        let grandchild = add(&conn, "Grandchild", Some(&child));
+        let target = add(&conn, "Target", None);
+
+        let err = remove_task(&conn, &root, ChildPolicy::Reparent(&grandchild), false).unwrap_err();
+        assert!(err.to_string().contains("one of its subtasks"), "{}", err);
+
+        let removal = remove_task(&conn, &child, ChildPolicy::Reparent(&target), false).unwrap();
+        assert_eq!(removal.moved, [grandchild.as_str()]);
+        assert_eq!(removal.new_parent.as_deref(), Some(target.as_str()));
+        let grandchild_parent = get_task(&conn, &grandchild).unwrap().parent_id;
+        assert_eq!(grandchild_parent.as_deref(), Some(target.as_str()));
+
+        let removal = remove_task(&conn, &target, ChildPolicy::Orphan, false).unwrap();
+        assert_eq!(removal.moved, [grandchild.as_str()]);
+        assert_eq!(get_task(&conn, &grandchild).unwrap().parent_id, None);
+    }
+
+    #[test]
+    fn remove_refuses_while_active_tasks_depend_on_it() {
+        let conn = test_db();
+        let dependency = add(&conn, "Dependency", None);
+        let depends = [dependency.clone()];
+        let task = NewTask {
+            title: "Dependent",
+            description: "",
+            depends: &depends,
+            ..Default::default()
+        };
+        let dependent = create_task(&conn, &task).unwrap().id;
+
+        let preview = remove_task(&conn, &dependency, ChildPolicy::Refuse, true).unwrap();
+        assert_eq!(preview.blocked, [dependent.as_str()]);
+
+        let err = remove_task(&conn, &dependency, ChildPolicy::Refuse, false).unwrap_err();
+        assert!(err.to_string().contains(&dependent), "{}", err);
+        assert!(task_exists(&conn, &dependency).unwrap());
+    }
+}
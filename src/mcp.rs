@@ -3,14 +3,22 @@
 //! Provides JSON-RPC 2.0 interface over stdio for IDE integration.
 
 use crate::{
-    complete_task, create_memory, create_task, get_memory, get_task, init_project, list_memories,
-    list_tasks, open_db, remove_memory, remove_task, search_memories, start_task, update_task,
+    add_comment, add_dependency, add_label, agenda, cancel_task, claim_task, complete_task,
+    create_memory, create_task, current_actor, doctor, edit_memory, edit_task, get_memory,
+    get_task, heartbeat_task, init_project, link_memory, list_memories, list_tasks, memory_tags,
+    next_task, open_db, pin_memory, project_context, remove_dependency, remove_label,
+    remove_memory, remove_task, reopen_task, search, search_memories, set_actor, set_status,
+    start_task, stop_task, task_history, task_tree, undo, unlink_memory, workflow, ChildPolicy,
+    DuplicateError, DuplicatePolicy, MemoryEdit, MemoryFilter, MemoryKind, NewMemory, NewTask, SearchKind,
+    TaskEdit, TaskFilter,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::io::{self, BufRead, Write};
+use std::sync::Mutex;
 
 const PROTOCOL_VERSION: &str = "2024-11-05";
 const SERVER_NAME: &str = "tsk";
@@ -19,6 +27,18 @@ const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
 const NOT_INITIALIZED_ERROR: &str =
     "Project not initialized. Run 'tsk init' in terminal or use the 'init' tool.";
 
+/// Tasks claimed or started through this server, with their assignee. A
+/// server process serves one client session, so `heartbeat` can default
+/// `as` for these and no others.
+static SESSION_CLAIMS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Remember the assignee of a task claimed or started in this session
+fn remember_claim(id: &str, assignee: Option<&str>) {
+    if let (Some(assignee), Ok(mut claims)) = (assignee, SESSION_CLAIMS.lock()) {
+        claims.insert(id.to_string(), assignee.to_string());
+    }
+}
+
 // ============================================================================
 // JSON-RPC Types
 // ============================================================================
@@ -176,7 +196,7 @@ fn get_tools() -> Vec<Tool> {
         },
         Tool {
             name: "create".to_string(),
-            description: "Create a new task".to_string(),
+            description: "Create a new task; returns its id plus any similar open tasks under the same parent (consider updating one of those instead). Set unique to refuse when a close match exists".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -193,8 +213,35 @@ fn get_tools() -> Vec<Tool> {
                         "description": "Parent task ID for subtasks"
                     },
                     "depend": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Dependency task IDs (all must be done first)"
+                    },
+                    "priority": {
+                        "type": "integer",
+                        "description": "Priority (higher is more urgent, default 0)"
+                    },
+                    "labels": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Labels (e.g. bug, feature, frontend)"
+                    },
+                    "label": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Alias of labels"
+                    },
+                    "due": {
                         "type": "string",
-                        "description": "Dependency task ID"
+                        "description": "Due date: YYYY-MM-DD, today, tomorrow, +3d or +2w"
+                    },
+                    "force": {
+                        "type": "boolean",
+                        "description": "Create even if a similar open task exists and the project refuses duplicates"
+                    },
+                    "unique": {
+                        "type": "boolean",
+                        "description": "Refuse if a similar open task exists under the same parent"
                     }
                 },
                 "required": ["title", "description"]
@@ -217,13 +264,37 @@ fn get_tools() -> Vec<Tool> {
                     "parent": {
                         "type": "string",
                         "description": "Filter by parent task ID"
+                    },
+                    "status": {
+                        "type": "string",
+                        "description": "Filter by workflow state name (see 'workflow' tool)"
+                    },
+                    "label": {
+                        "type": "string",
+                        "description": "Filter by label"
+                    },
+                    "labels": {
+                        "type": "string",
+                        "description": "Alias of label"
+                    },
+                    "overdue": {
+                        "type": "boolean",
+                        "description": "Only open tasks past their due date"
+                    },
+                    "due_before": {
+                        "type": "string",
+                        "description": "Only tasks due before this date (YYYY-MM-DD, today, tomorrow, +3d, +2w)"
+                    },
+                    "assignee": {
+                        "type": "string",
+                        "description": "Only open tasks claimed by this agent"
                     }
                 }
             }),
         },
         Tool {
             name: "show".to_string(),
-            description: "Show full task details".to_string(),
+            description: "Show full task details, including comments".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -237,7 +308,7 @@ fn get_tools() -> Vec<Tool> {
         },
         Tool {
             name: "update".to_string(),
-            description: "Update task description".to_string(),
+            description: "Update task fields. Only the given fields change".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -245,23 +316,172 @@ fn get_tools() -> Vec<Tool> {
                         "type": "string",
                         "description": "Task ID (6 characters)"
                     },
+                    "title": {
+                        "type": "string",
+                        "description": "New title"
+                    },
                     "description": {
                         "type": "string",
                         "description": "New description text"
+                    },
+                    "priority": {
+                        "type": "integer",
+                        "description": "New priority (higher is more urgent)"
+                    },
+                    "parent": {
+                        "type": ["string", "null"],
+                        "description": "New parent task ID, or null to remove the parent"
+                    },
+                    "depend": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Replace dependencies with these task IDs (empty array clears them)"
+                    },
+                    "due": {
+                        "type": ["string", "null"],
+                        "description": "New due date (YYYY-MM-DD, today, tomorrow, +3d, +2w); null clears it"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+        Tool {
+            name: "context".to_string(),
+            description: "Project digest for the start of a session: in-progress tasks with descriptions, ready tasks, pinned and relevant memories, recent activity (markdown)"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "budget": {
+                        "type": "integer",
+                        "description": "Approximate maximum size in tokens (default 2000)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "next".to_string(),
+            description: "Get the next actionable task: highest-priority pending task whose dependencies are all done"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "tree".to_string(),
+            description: "Parent/child task hierarchy as nested JSON (each task has a 'children' array)".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "root": {
+                        "type": "string",
+                        "description": "Root task ID; omit for all top-level tasks"
+                    },
+                    "depth": {
+                        "type": "integer",
+                        "description": "Maximum depth below the root (0 = root only)"
+                    },
+                    "all": {
+                        "type": "boolean",
+                        "description": "Include done and cancelled tasks without open subtasks"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "search".to_string(),
+            description: "Ranked full-text search over task titles, descriptions and memories. Every word must match (as a prefix); snippets mark matches with **"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Search words"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["task", "memory"],
+                        "description": "Only tasks or only memories"
+                    },
+                    "status": {
+                        "type": "string",
+                        "description": "Only tasks in this workflow state"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of results (default 20)"
                     }
                 },
-                "required": ["id", "description"]
+                "required": ["query"]
+            }),
+        },
+        Tool {
+            name: "claim".to_string(),
+            description: "Atomically start a task and assign it to an agent. Without id, claims the next actionable task; returns the claimed task or null. Parallel agents never get the same task."
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters); omit to claim the next ready task"
+                    },
+                    "as": {
+                        "type": "string",
+                        "description": "Agent name to assign (default: the MCP client name); use a distinct name per parallel agent"
+                    },
+                    "lease": {
+                        "type": "string",
+                        "description": "Lease length (e.g. 30m, 2h); the task returns to pending unless renewed with 'heartbeat'"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "agenda".to_string(),
+            description: "Open tasks with a due date, grouped by day (earliest first)".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
             }),
         },
         Tool {
             name: "start".to_string(),
-            description: "Start working on a task (pending -> in progress)".to_string(),
+            description: "Start working on a task (pending -> in progress) and assign it to this session".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters)"
+                    },
+                    "lease": {
+                        "type": "string",
+                        "description": "Lease length (e.g. 30m, 2h); the task returns to pending unless renewed with 'heartbeat'"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+        Tool {
+            name: "heartbeat".to_string(),
+            description: "Renew the lease on an in-progress task".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "id": {
                         "type": "string",
                         "description": "Task ID (6 characters)"
+                    },
+                    "as": {
+                        "type": "string",
+                        "description": "Agent holding the task; must match the assignee. Required unless the task was claimed or started in this session, whose assignee is the default"
+                    },
+                    "lease": {
+                        "type": "string",
+                        "description": "New lease length (default: the original length)"
                     }
                 },
                 "required": ["id"]
@@ -281,102 +501,512 @@ fn get_tools() -> Vec<Tool> {
                 "required": ["id"]
             }),
         },
+        Tool {
+            name: "reopen".to_string(),
+            description: "Reopen a done or cancelled task (back to pending). Returns completed dependents as a warning"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters)"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+        Tool {
+            name: "stop".to_string(),
+            description: "Stop working on a task (in progress -> pending)".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters)"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+        Tool {
+            name: "cancel".to_string(),
+            description: "Cancel a task. Returns active dependents that stay blocked".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters)"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+        Tool {
+            name: "set_status".to_string(),
+            description: "Move task to any workflow state allowed by the project's transitions"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters)"
+                    },
+                    "status": {
+                        "type": "string",
+                        "description": "Workflow state name"
+                    }
+                },
+                "required": ["id", "status"]
+            }),
+        },
+        Tool {
+            name: "workflow".to_string(),
+            description: "Show workflow states (name, marker, kind) and allowed transitions"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
         Tool {
             name: "remove".to_string(),
-            description: "Remove a task".to_string(),
+            description: "Remove a task. A task with subtasks needs recursive, \
+                orphan_children or reparent_to"
+                .to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "id": {
                         "type": "string",
                         "description": "Task ID (6 characters)"
+                    },
+                    "recursive": {
+                        "type": "boolean",
+                        "description": "Remove the task and all of its subtasks"
+                    },
+                    "orphan_children": {
+                        "type": "boolean",
+                        "description": "Make the subtasks top-level tasks"
+                    },
+                    "reparent_to": {
+                        "type": "string",
+                        "description": "Move the subtasks under this task ID"
+                    },
+                    "dry_run": {
+                        "type": "boolean",
+                        "description": "Preview what would be removed, and which active dependents block it, without changing anything"
                     }
                 },
                 "required": ["id"]
             }),
         },
-        // Memory tools
         Tool {
-            name: "memory_create".to_string(),
-            description: "Create a memory entry to store project knowledge".to_string(),
+            name: "depend_add".to_string(),
+            description: "Add a dependency: task can't be done until depend task is done"
+                .to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
-                    "content": {
+                    "id": {
                         "type": "string",
-                        "description": "Memory content text"
+                        "description": "Task ID (6 characters)"
                     },
-                    "tags": {
+                    "depend": {
                         "type": "string",
-                        "description": "Tags (comma-separated)"
+                        "description": "Dependency task ID"
                     }
                 },
-                "required": ["content"]
+                "required": ["id", "depend"]
             }),
         },
         Tool {
-            name: "memory_list".to_string(),
-            description: "List memory entries".to_string(),
+            name: "depend_remove".to_string(),
+            description: "Remove a dependency from a task".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
-                    "tag": {
+                    "id": {
                         "type": "string",
-                        "description": "Filter by tag"
+                        "description": "Task ID (6 characters)"
                     },
-                    "last": {
-                        "type": "integer",
-                        "description": "Show only last N entries"
+                    "depend": {
+                        "type": "string",
+                        "description": "Dependency task ID"
                     }
-                }
+                },
+                "required": ["id", "depend"]
             }),
         },
         Tool {
-            name: "memory_show".to_string(),
-            description: "Show full memory entry".to_string(),
+            name: "label_add".to_string(),
+            description: "Add a label to a task".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "id": {
                         "type": "string",
-                        "description": "Memory ID (6 characters)"
+                        "description": "Task ID (6 characters)"
+                    },
+                    "label": {
+                        "type": "string",
+                        "description": "Label name"
+                    },
+                    "labels": {
+                        "type": "string",
+                        "description": "Alias of label"
                     }
                 },
                 "required": ["id"]
             }),
         },
         Tool {
-            name: "memory_search".to_string(),
-            description: "Search memories by content".to_string(),
+            name: "label_remove".to_string(),
+            description: "Remove a label from a task".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
-                    "query": {
+                    "id": {
                         "type": "string",
-                        "description": "Search query"
+                        "description": "Task ID (6 characters)"
+                    },
+                    "label": {
+                        "type": "string",
+                        "description": "Label name"
+                    },
+                    "labels": {
+                        "type": "string",
+                        "description": "Alias of label"
                     }
                 },
-                "required": ["query"]
+                "required": ["id"]
             }),
         },
         Tool {
-            name: "memory_remove".to_string(),
-            description: "Remove a memory entry".to_string(),
+            name: "comment".to_string(),
+            description: "Add a comment (progress note) to a task".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "id": {
                         "type": "string",
-                        "description": "Memory ID (6 characters)"
+                        "description": "Task ID (6 characters)"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Comment text"
+                    }
+                },
+                "required": ["id", "text"]
+            }),
+        },
+        Tool {
+            name: "comments".to_string(),
+            description: "List comments on a task (oldest first)".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters)"
                     }
                 },
                 "required": ["id"]
             }),
         },
-    ]
-}
-
-// ============================================================================
+        Tool {
+            name: "link".to_string(),
+            description: "Link a memory to a task; show lists a task's linked memories".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "task_id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters)"
+                    },
+                    "memory_id": {
+                        "type": "string",
+                        "description": "Memory ID (6 characters)"
+                    }
+                },
+                "required": ["task_id", "memory_id"]
+            }),
+        },
+        Tool {
+            name: "unlink".to_string(),
+            description: "Remove the link between a memory and a task".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "task_id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters)"
+                    },
+                    "memory_id": {
+                        "type": "string",
+                        "description": "Memory ID (6 characters)"
+                    }
+                },
+                "required": ["task_id", "memory_id"]
+            }),
+        },
+        Tool {
+            name: "history".to_string(),
+            description: "Show task event log (created, updated, started, completed, removed) with timestamps and actors"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Task ID (6 characters); omit with all=true for every task"
+                    },
+                    "all": {
+                        "type": "boolean",
+                        "description": "Show events for all tasks"
+                    },
+                    "last": {
+                        "type": "integer",
+                        "description": "Show only last N events"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "undo".to_string(),
+            description: "Undo the last N changes to tasks and memories (create, update, start, done, remove, ...), newest first"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "count": {
+                        "type": "integer",
+                        "description": "Number of changes to undo (default 1)"
+                    },
+                    "dry_run": {
+                        "type": "boolean",
+                        "description": "List the changes that would be undone without undoing them"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "doctor".to_string(),
+            description: "Check task graph integrity: orphaned parent/dependency links, cycles, invalid statuses, duplicate IDs"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "fix": {
+                        "type": "boolean",
+                        "description": "Repair the problems found"
+                    }
+                }
+            }),
+        },
+        // Memory tools
+        Tool {
+            name: "memory_create".to_string(),
+            description: "Store project knowledge: a note, decision, convention or gotcha. Returns its id plus any similar entries (consider memory_update on one of those instead). Set unique to refuse when a close match exists".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "content": {
+                        "type": "string",
+                        "description": "Memory content text"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["decision", "convention", "gotcha"],
+                        "description": "Kind of entry (omit for a plain note)"
+                    },
+                    "tags": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Tags (lowercased, spaces become hyphens, no commas)"
+                    },
+                    "tasks": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Task IDs to link the memory to"
+                    },
+                    "review_after": {
+                        "type": "string",
+                        "description": "Check the entry again from this date (YYYY-MM-DD, today, +3d, +2w)"
+                    },
+                    "expires_at": {
+                        "type": "string",
+                        "description": "The entry no longer holds from this date (YYYY-MM-DD, +3d, +2w)"
+                    },
+                    "force": {
+                        "type": "boolean",
+                        "description": "Create even if a similar entry exists and the project refuses duplicates"
+                    },
+                    "unique": {
+                        "type": "boolean",
+                        "description": "Refuse if a similar entry exists"
+                    }
+                },
+                "required": ["content"]
+            }),
+        },
+        Tool {
+            name: "memory_list".to_string(),
+            description: "List memory entries, newest first; entries marked stale may be outdated".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "tags": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Only entries with all of these tags (exact match)"
+                    },
+                    "tag": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Alias of tags"
+                    },
+                    "any": {
+                        "type": "boolean",
+                        "description": "Match entries with any of the tags instead of all"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["decision", "convention", "gotcha"],
+                        "description": "Only entries of this kind"
+                    },
+                    "stale": {
+                        "type": "boolean",
+                        "description": "Only entries whose review or expiry date has come (check or update them before relying on them)"
+                    },
+                    "last": {
+                        "type": "integer",
+                        "description": "Show only last N entries"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "memory_tags".to_string(),
+            description: "List memory tags with the number of entries using each".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "memory_show".to_string(),
+            description: "Show full memory entry".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Memory ID (6 characters)"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+        Tool {
+            name: "memory_search".to_string(),
+            description: "Search memories by content".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Search query"
+                    }
+                },
+                "required": ["query"]
+            }),
+        },
+        Tool {
+            name: "memory_update".to_string(),
+            description: "Edit a memory entry in place (keeps its ID): content, kind, tags and/or dates".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Memory ID (6 characters)"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "New content"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["decision", "convention", "gotcha", ""],
+                        "description": "New kind (empty string makes it a plain note)"
+                    },
+                    "review_after": {
+                        "type": "string",
+                        "description": "New review date (YYYY-MM-DD, today, +3d, +2w; empty string clears it)"
+                    },
+                    "expires_at": {
+                        "type": "string",
+                        "description": "New expiry date (YYYY-MM-DD, today, +3d, +2w; empty string clears it)"
+                    },
+                    "tags": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Replace all tags (empty array clears them)"
+                    },
+                    "add_tags": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Tags to add"
+                    },
+                    "remove_tags": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Tags to remove"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+        Tool {
+            name: "memory_pin".to_string(),
+            description: "Pin a memory entry so it is always included in context, or unpin it".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Memory ID (6 characters)"
+                    },
+                    "pinned": {
+                        "type": "boolean",
+                        "description": "false to unpin (default true)"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+        Tool {
+            name: "memory_remove".to_string(),
+            description: "Remove a memory entry".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Memory ID (6 characters)"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+    ]
+}
+
+// ============================================================================
 // Tool Handlers
 // ============================================================================
 
@@ -400,14 +1030,40 @@ fn handle_tool_with_db(conn: &Connection, name: &str, args: &Value) -> ToolResul
         "list" => handle_list(conn, args),
         "show" => handle_show(conn, args),
         "update" => handle_update(conn, args),
+        "context" => handle_context(conn, args),
+        "next" => handle_next(conn),
+        "tree" => handle_tree(conn, args),
+        "search" => handle_search(conn, args),
+        "claim" => handle_claim(conn, args),
+        "heartbeat" => handle_heartbeat(conn, args),
+        "agenda" => handle_agenda(conn),
         "start" => handle_start(conn, args),
         "done" => handle_done(conn, args),
+        "reopen" => handle_reopen(conn, args),
+        "stop" => handle_stop(conn, args),
+        "cancel" => handle_cancel(conn, args),
+        "set_status" => handle_set_status(conn, args),
+        "workflow" => handle_workflow(),
         "remove" => handle_remove(conn, args),
+        "depend_add" => handle_depend_add(conn, args),
+        "depend_remove" => handle_depend_remove(conn, args),
+        "label_add" => handle_label_add(conn, args),
+        "label_remove" => handle_label_remove(conn, args),
+        "comment" => handle_comment(conn, args),
+        "comments" => handle_comments(conn, args),
+        "link" => handle_link(conn, args, true),
+        "unlink" => handle_link(conn, args, false),
+        "history" => handle_history(conn, args),
+        "undo" => handle_undo(conn, args),
+        "doctor" => handle_doctor(conn, args),
         // Memory tools
         "memory_create" => handle_memory_create(conn, args),
         "memory_list" => handle_memory_list(conn, args),
+        "memory_tags" => handle_memory_tags(conn),
         "memory_show" => handle_memory_show(conn, args),
         "memory_search" => handle_memory_search(conn, args),
+        "memory_update" => handle_memory_update(conn, args),
+        "memory_pin" => handle_memory_pin(conn, args),
         "memory_remove" => handle_memory_remove(conn, args),
         _ => ToolResult::error(format!("Unknown tool: {}", name)),
     }
@@ -423,15 +1079,124 @@ fn handle_init() -> ToolResult {
     }
 }
 
+/// Read a string-array argument, also accepting a single string
+fn string_list(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Read a tags argument: an array, or a comma-separated string from older clients
+fn tag_list(value: &Value) -> Vec<String> {
+    string_list(value)
+        .iter()
+        .flat_map(|tags| tags.split(','))
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Read the labels of a create call; `label` is accepted as an alias of `labels`
+fn label_list(args: &Value) -> Vec<String> {
+    let mut labels = string_list(&args["labels"]);
+    labels.extend(string_list(&args["label"]));
+    labels
+}
+
+/// Read the single label of a list or label tool; `labels` is accepted as an alias of `label`
+fn single_label(args: &Value) -> Result<Option<String>> {
+    let mut labels = string_list(&args["label"]);
+    labels.extend(string_list(&args["labels"]));
+    labels.dedup();
+    match labels.len() {
+        0 => Ok(None),
+        1 => Ok(labels.pop()),
+        _ => bail!("Expected one label, got {}.", labels.join(", ")),
+    }
+}
+
+/// Read the `force` / `unique` arguments of the create tools
+fn duplicate_policy(args: &Value) -> Option<DuplicatePolicy> {
+    DuplicatePolicy::from_flags(
+        args["force"].as_bool().unwrap_or(false),
+        args["unique"].as_bool().unwrap_or(false),
+    )
+}
+
+/// Read an optional memory kind argument
+fn memory_kind(value: &Value) -> Result<Option<MemoryKind>> {
+    value.as_str().map(MemoryKind::parse).transpose()
+}
+
+/// Read an argument that is either a non-empty string or null (clear it);
+/// `None` when the argument is absent
+fn nullable_str<'a>(args: &'a Value, name: &str) -> Result<Option<Option<&'a str>>> {
+    match args.get(name) {
+        None => Ok(None),
+        Some(Value::Null) => Ok(Some(None)),
+        Some(Value::String(s)) if !s.trim().is_empty() => Ok(Some(Some(s))),
+        Some(other) => bail!("Invalid {} {}. Use a string, or null to clear it.", name, other),
+    }
+}
+
+/// Read an optional priority argument, rejecting values that don't fit an i32
+fn priority(value: &Value) -> Result<Option<i32>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    match value.as_i64().map(i32::try_from) {
+        Some(Ok(priority)) => Ok(Some(priority)),
+        _ => bail!(
+            "Invalid priority {}. Use an integer between {} and {}.",
+            value,
+            i32::MIN,
+            i32::MAX
+        ),
+    }
+}
+
 fn handle_create(conn: &Connection, args: &Value) -> ToolResult {
-    let title = args["title"].as_str().unwrap_or_default();
-    let description = args["description"].as_str().unwrap_or_default();
-    let parent = args["parent"].as_str();
-    let depend = args["depend"].as_str();
+    let depends = string_list(&args["depend"]);
+    let labels = label_list(args);
+    let priority = match priority(&args["priority"]) {
+        Ok(priority) => priority.unwrap_or(0),
+        Err(e) => return ToolResult::error(e.to_string()),
+    };
 
-    match create_task(conn, title, description, parent, depend) {
-        Ok(id) => ToolResult::json(&json!({ "id": id })),
-        Err(e) => ToolResult::error(e.to_string()),
+    let task = NewTask {
+        title: args["title"].as_str().unwrap_or_default(),
+        description: args["description"].as_str().unwrap_or_default(),
+        parent: args["parent"].as_str(),
+        depends: &depends,
+        priority,
+        labels: &labels,
+        due: args["due"].as_str(),
+        duplicates: duplicate_policy(args),
+    };
+
+    match create_task(conn, &task) {
+        Ok(created) => ToolResult::json(&created),
+        Err(e) => create_error(e),
+    }
+}
+
+/// Error result for create tools; close matches are returned as JSON so the
+/// agent can update one of them instead
+fn create_error(e: anyhow::Error) -> ToolResult {
+    match e.downcast_ref::<DuplicateError>() {
+        Some(DuplicateError(duplicates)) => {
+            let result = json!({
+                "error": "Similar entries already exist. Update one of them, or set force to create anyway.",
+                "duplicates": duplicates,
+            });
+            ToolResult::error(serde_json::to_string_pretty(&result).unwrap_or_default())
+        }
+        None => ToolResult::error(e.to_string()),
     }
 }
 
@@ -439,8 +1204,27 @@ fn handle_list(conn: &Connection, args: &Value) -> ToolResult {
     let inprogress = args["inprogress"].as_bool().unwrap_or(false);
     let all = args["all"].as_bool().unwrap_or(false);
     let parent = args["parent"].as_str();
+    let status = args["status"].as_str();
+    let label = match single_label(args) {
+        Ok(label) => label,
+        Err(e) => return ToolResult::error(e.to_string()),
+    };
+    let overdue = args["overdue"].as_bool().unwrap_or(false);
+    let due_before = args["due_before"].as_str();
+    let assignee = args["assignee"].as_str();
+
+    let filter = TaskFilter {
+        inprogress,
+        all,
+        parent,
+        status,
+        label: label.as_deref(),
+        overdue,
+        due_before,
+        assignee,
+    };
 
-    match list_tasks(conn, inprogress, all, parent) {
+    match list_tasks(conn, &filter) {
         Ok(tasks) => ToolResult::json(&tasks),
         Err(e) => ToolResult::error(e.to_string()),
     }
@@ -463,22 +1247,134 @@ fn handle_update(conn: &Connection, args: &Value) -> ToolResult {
         Some(id) => id,
         None => return ToolResult::error("Missing required parameter: id"),
     };
-    let description = args["description"].as_str().unwrap_or_default();
+    let depends = args.get("depend").map(string_list);
+    let priority = match priority(&args["priority"]) {
+        Ok(priority) => priority,
+        Err(e) => return ToolResult::error(e.to_string()),
+    };
+    let (parent, due) = match (nullable_str(args, "parent"), nullable_str(args, "due")) {
+        (Ok(parent), Ok(due)) => (parent, due),
+        (Err(e), _) | (_, Err(e)) => return ToolResult::error(e.to_string()),
+    };
 
-    match update_task(conn, id, description) {
+    let changes = TaskEdit {
+        title: args["title"].as_str(),
+        description: args["description"].as_str(),
+        priority,
+        parent,
+        depends: depends.as_deref(),
+        due,
+    };
+
+    match edit_task(conn, id, &changes) {
         Ok(()) => ToolResult::json(&json!({ "success": true, "id": id })),
         Err(e) => ToolResult::error(e.to_string()),
     }
 }
 
+fn handle_context(conn: &Connection, args: &Value) -> ToolResult {
+    let budget = args["budget"].as_u64().unwrap_or(2000) as usize;
+
+    match project_context(conn, budget) {
+        Ok(digest) => ToolResult::text(digest),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_next(conn: &Connection) -> ToolResult {
+    match next_task(conn) {
+        Ok(task) => ToolResult::json(&task),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_tree(conn: &Connection, args: &Value) -> ToolResult {
+    let root = args["root"].as_str();
+    let depth = args["depth"].as_u64().map(|d| d as usize);
+    let all = args["all"].as_bool().unwrap_or(false);
+
+    match task_tree(conn, root, depth, all) {
+        Ok(nodes) => ToolResult::json(&nodes),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_search(conn: &Connection, args: &Value) -> ToolResult {
+    let query = match args["query"].as_str() {
+        Some(q) => q,
+        None => return ToolResult::error("Missing required parameter: query"),
+    };
+    let kind = match args["kind"].as_str() {
+        None => None,
+        Some("task") => Some(SearchKind::Task),
+        Some("memory") => Some(SearchKind::Memory),
+        Some(other) => {
+            return ToolResult::error(format!("Unknown kind '{}'. Valid: task, memory.", other));
+        }
+    };
+    let status = args["status"].as_str();
+    let limit = args["limit"].as_u64().unwrap_or(20) as usize;
+
+    match search(conn, query, kind, status, limit) {
+        Ok(hits) => ToolResult::json(&hits),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_claim(conn: &Connection, args: &Value) -> ToolResult {
+    let agent = args["as"].as_str().map(str::to_string).unwrap_or_else(current_actor);
+
+    let claimed = claim_task(conn, args["id"].as_str(), &agent, args["lease"].as_str())
+        .and_then(|id| id.map(|id| get_task(conn, &id)).transpose());
+    match claimed {
+        Ok(task) => {
+            if let Some(task) = &task {
+                remember_claim(&task.id, task.assignee.as_deref());
+            }
+            ToolResult::json(&task)
+        }
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_agenda(conn: &Connection) -> ToolResult {
+    match agenda(conn) {
+        Ok(days) => ToolResult::json(&days),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
 fn handle_start(conn: &Connection, args: &Value) -> ToolResult {
     let id = match args["id"].as_str() {
         Some(id) => id,
         None => return ToolResult::error("Missing required parameter: id"),
     };
 
-    match start_task(conn, id) {
-        Ok(()) => ToolResult::json(&json!({ "success": true, "id": id })),
+    let started = start_task(conn, id, args["lease"].as_str()).and_then(|()| get_task(conn, id));
+    match started {
+        Ok(task) => {
+            remember_claim(id, task.assignee.as_deref());
+            ToolResult::json(&json!({ "success": true, "id": id, "lease_until": task.lease_until }))
+        }
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_heartbeat(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+
+    let session_agent = || SESSION_CLAIMS.lock().ok()?.get(id).cloned();
+    let Some(agent) = args["as"].as_str().map(str::to_string).or_else(session_agent) else {
+        return ToolResult::error(
+            "Missing required parameter: as (the agent holding the task; only optional for tasks claimed or started in this session)",
+        );
+    };
+
+    match heartbeat_task(conn, id, &agent, args["lease"].as_str()) {
+        Ok(until) => ToolResult::json(&json!({ "success": true, "id": id, "lease_until": until })),
         Err(e) => ToolResult::error(e.to_string()),
     }
 }
@@ -490,19 +1386,275 @@ fn handle_done(conn: &Connection, args: &Value) -> ToolResult {
     };
 
     match complete_task(conn, id) {
+        Ok(parents) => ToolResult::json(&json!({
+            "success": true,
+            "id": id,
+            "auto_completed_parents": parents
+        })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_reopen(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+
+    match reopen_task(conn, id) {
+        Ok(completed) => ToolResult::json(&json!({
+            "success": true,
+            "id": id,
+            "completed_dependents": completed
+        })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_stop(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+
+    match stop_task(conn, id) {
         Ok(()) => ToolResult::json(&json!({ "success": true, "id": id })),
         Err(e) => ToolResult::error(e.to_string()),
     }
 }
 
+fn handle_cancel(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+
+    match cancel_task(conn, id) {
+        Ok((blocked, parents)) => ToolResult::json(&json!({
+            "success": true,
+            "id": id,
+            "blocked_dependents": blocked,
+            "auto_completed_parents": parents
+        })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_set_status(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+    let status = match args["status"].as_str() {
+        Some(s) => s,
+        None => return ToolResult::error("Missing required parameter: status"),
+    };
+
+    match set_status(conn, id, status) {
+        Ok(parents) => ToolResult::json(&json!({
+            "success": true,
+            "id": id,
+            "status": status,
+            "auto_completed_parents": parents
+        })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_workflow() -> ToolResult {
+    match workflow() {
+        Ok(workflow) => ToolResult::json(workflow),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
 fn handle_remove(conn: &Connection, args: &Value) -> ToolResult {
     let id = match args["id"].as_str() {
         Some(id) => id,
         None => return ToolResult::error("Missing required parameter: id"),
     };
 
-    match remove_task(conn, id) {
-        Ok(()) => ToolResult::json(&json!({ "success": true, "id": id })),
+    let recursive = args["recursive"].as_bool().unwrap_or(false);
+    let orphan_children = args["orphan_children"].as_bool().unwrap_or(false);
+    let reparent_to = args["reparent_to"].as_str();
+    let dry_run = args["dry_run"].as_bool().unwrap_or(false);
+    let children = match (recursive, orphan_children, reparent_to) {
+        (false, false, None) => ChildPolicy::Refuse,
+        (true, false, None) => ChildPolicy::Recursive,
+        (false, true, None) => ChildPolicy::Orphan,
+        (false, false, Some(to)) => ChildPolicy::Reparent(to),
+        _ => {
+            return ToolResult::error(
+                "Use only one of recursive, orphan_children and reparent_to",
+            );
+        }
+    };
+
+    match remove_task(conn, id, children, dry_run) {
+        Ok(removal) => ToolResult::json(&json!({
+            "success": true,
+            "id": id,
+            "dry_run": dry_run,
+            "removed": removal.removed,
+            "moved": removal.moved,
+            "new_parent": removal.new_parent,
+            "blocked": removal.blocked,
+        })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_depend_add(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+    let depend = match args["depend"].as_str() {
+        Some(d) => d,
+        None => return ToolResult::error("Missing required parameter: depend"),
+    };
+
+    match add_dependency(conn, id, depend) {
+        Ok(()) => ToolResult::json(&json!({ "success": true, "id": id, "depend": depend })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_depend_remove(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+    let depend = match args["depend"].as_str() {
+        Some(d) => d,
+        None => return ToolResult::error("Missing required parameter: depend"),
+    };
+
+    match remove_dependency(conn, id, depend) {
+        Ok(()) => ToolResult::json(&json!({ "success": true, "id": id, "depend": depend })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_label_add(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+    let label = match single_label(args) {
+        Ok(Some(label)) => label,
+        Ok(None) => return ToolResult::error("Missing required parameter: label"),
+        Err(e) => return ToolResult::error(e.to_string()),
+    };
+
+    match add_label(conn, id, &label) {
+        Ok(label) => ToolResult::json(&json!({ "success": true, "id": id, "label": label })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_label_remove(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+    let label = match single_label(args) {
+        Ok(Some(label)) => label,
+        Ok(None) => return ToolResult::error("Missing required parameter: label"),
+        Err(e) => return ToolResult::error(e.to_string()),
+    };
+
+    match remove_label(conn, id, &label) {
+        Ok(label) => ToolResult::json(&json!({ "success": true, "id": id, "label": label })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_comment(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+    let text = match args["text"].as_str() {
+        Some(t) => t,
+        None => return ToolResult::error("Missing required parameter: text"),
+    };
+
+    match add_comment(conn, id, text) {
+        Ok(comment_id) => {
+            ToolResult::json(&json!({ "success": true, "id": id, "comment_id": comment_id }))
+        }
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_comments(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+
+    match get_task(conn, id) {
+        Ok(task) => ToolResult::json(&task.comments),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_link(conn: &Connection, args: &Value, link: bool) -> ToolResult {
+    let task_id = match args["task_id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: task_id"),
+    };
+    let memory_id = match args["memory_id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: memory_id"),
+    };
+
+    let result = if link {
+        link_memory(conn, task_id, memory_id)
+    } else {
+        unlink_memory(conn, task_id, memory_id)
+    };
+    match result {
+        Ok(()) => ToolResult::json(&json!({
+            "success": true,
+            "task_id": task_id,
+            "memory_id": memory_id
+        })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_history(conn: &Connection, args: &Value) -> ToolResult {
+    let id = args["id"].as_str();
+    let all = args["all"].as_bool().unwrap_or(false);
+    let last = args["last"].as_u64().map(|n| n as usize);
+
+    if id.is_none() && !all {
+        return ToolResult::error("Missing required parameter: id (or all=true)");
+    }
+
+    match task_history(conn, id, last) {
+        Ok(events) => ToolResult::json(&events),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_undo(conn: &Connection, args: &Value) -> ToolResult {
+    let count = args["count"].as_u64().unwrap_or(1) as usize;
+    let dry_run = args["dry_run"].as_bool().unwrap_or(false);
+
+    match undo(conn, count, dry_run) {
+        Ok(entries) => ToolResult::json(&json!({ "dry_run": dry_run, "undone": entries })),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_doctor(conn: &Connection, args: &Value) -> ToolResult {
+    let fix = args["fix"].as_bool().unwrap_or(false);
+
+    match doctor(conn, fix) {
+        Ok(issues) => ToolResult::json(&issues),
         Err(e) => ToolResult::error(e.to_string()),
     }
 }
@@ -513,24 +1665,57 @@ fn handle_remove(conn: &Connection, args: &Value) -> ToolResult {
 
 fn handle_memory_create(conn: &Connection, args: &Value) -> ToolResult {
     let content = args["content"].as_str().unwrap_or_default();
-    let tags = args["tags"].as_str();
+    let tags = tag_list(&args["tags"]);
+    let tasks = string_list(&args["tasks"]);
+    let kind = match memory_kind(&args["kind"]) {
+        Ok(kind) => kind,
+        Err(e) => return ToolResult::error(e.to_string()),
+    };
+    let memory = NewMemory {
+        content,
+        kind,
+        tags: &tags,
+        review_after: args["review_after"].as_str(),
+        expires_at: args["expires_at"].as_str(),
+        duplicates: duplicate_policy(args),
+        tasks: &tasks,
+    };
 
-    match create_memory(conn, content, tags) {
-        Ok(id) => ToolResult::json(&json!({ "id": id })),
-        Err(e) => ToolResult::error(e.to_string()),
+    match create_memory(conn, &memory) {
+        Ok(created) => ToolResult::json(&created),
+        Err(e) => create_error(e),
     }
 }
 
 fn handle_memory_list(conn: &Connection, args: &Value) -> ToolResult {
-    let tag = args["tag"].as_str();
-    let last = args["last"].as_u64().map(|n| n as usize);
+    // `tag` is the older name of `tags`
+    let mut tags = tag_list(&args["tags"]);
+    tags.extend(tag_list(&args["tag"]));
+    let kind = match memory_kind(&args["kind"]) {
+        Ok(kind) => kind,
+        Err(e) => return ToolResult::error(e.to_string()),
+    };
+    let filter = MemoryFilter {
+        tags: &tags,
+        any: args["any"].as_bool().unwrap_or(false),
+        kind,
+        stale: args["stale"].as_bool().unwrap_or(false),
+        last: args["last"].as_u64().map(|n| n as usize),
+    };
 
-    match list_memories(conn, tag, last) {
+    match list_memories(conn, &filter) {
         Ok(memories) => ToolResult::json(&memories),
         Err(e) => ToolResult::error(e.to_string()),
     }
 }
 
+fn handle_memory_tags(conn: &Connection) -> ToolResult {
+    match memory_tags(conn) {
+        Ok(tags) => ToolResult::json(&tags),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
 fn handle_memory_show(conn: &Connection, args: &Value) -> ToolResult {
     let id = match args["id"].as_str() {
         Some(id) => id,
@@ -555,6 +1740,55 @@ fn handle_memory_search(conn: &Connection, args: &Value) -> ToolResult {
     }
 }
 
+fn handle_memory_update(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+    let tags = (!args["tags"].is_null()).then(|| tag_list(&args["tags"]));
+    let add_tags = tag_list(&args["add_tags"]);
+    let remove_tags = tag_list(&args["remove_tags"]);
+    let kind = match args["kind"].as_str() {
+        None => None,
+        Some("") => Some(None),
+        Some(kind) => match MemoryKind::parse(kind) {
+            Ok(kind) => Some(Some(kind)),
+            Err(e) => return ToolResult::error(e.to_string()),
+        },
+    };
+
+    // An empty string clears a date
+    let date = |name: &str| args[name].as_str().map(|d| Some(d).filter(|d| !d.is_empty()));
+
+    let changes = MemoryEdit {
+        content: args["content"].as_str(),
+        kind,
+        review_after: date("review_after"),
+        expires_at: date("expires_at"),
+        tags: tags.as_deref(),
+        add_tags: &add_tags,
+        remove_tags: &remove_tags,
+    };
+
+    match edit_memory(conn, id, &changes).and_then(|()| get_memory(conn, id)) {
+        Ok(memory) => ToolResult::json(&memory),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
+fn handle_memory_pin(conn: &Connection, args: &Value) -> ToolResult {
+    let id = match args["id"].as_str() {
+        Some(id) => id,
+        None => return ToolResult::error("Missing required parameter: id"),
+    };
+    let pinned = args["pinned"].as_bool().unwrap_or(true);
+
+    match pin_memory(conn, id, pinned).and_then(|()| get_memory(conn, id)) {
+        Ok(memory) => ToolResult::json(&memory),
+        Err(e) => ToolResult::error(e.to_string()),
+    }
+}
+
 fn handle_memory_remove(conn: &Connection, args: &Value) -> ToolResult {
     let id = match args["id"].as_str() {
         Some(id) => id,
@@ -575,20 +1809,24 @@ fn handle_request(request: JsonRpcRequest) -> Option<JsonRpcResponse> {
     let id = request.id.clone();
 
     match request.method.as_str() {
-        "initialize" => Some(JsonRpcResponse::success(
-            id,
-            serde_json::to_value(InitializeResult {
-                protocol_version: PROTOCOL_VERSION.to_string(),
-                capabilities: Capabilities {
-                    tools: ToolsCapability { list_changed: false },
-                },
-                server_info: ServerInfo {
-                    name: SERVER_NAME.to_string(),
-                    version: SERVER_VERSION.to_string(),
-                },
-            })
-            .unwrap(),
-        )),
+        "initialize" => {
+            let client = request.params["clientInfo"]["name"].as_str().unwrap_or("client");
+            set_actor(&format!("mcp:{}", client));
+            Some(JsonRpcResponse::success(
+                id,
+                serde_json::to_value(InitializeResult {
+                    protocol_version: PROTOCOL_VERSION.to_string(),
+                    capabilities: Capabilities {
+                        tools: ToolsCapability { list_changed: false },
+                    },
+                    server_info: ServerInfo {
+                        name: SERVER_NAME.to_string(),
+                        version: SERVER_VERSION.to_string(),
+                    },
+                })
+                .unwrap(),
+            ))
+        }
 
         "notifications/initialized" => None, // No response for notifications
 